@@ -1,31 +1,272 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     path::{Path, PathBuf},
+    process::Command,
+    io::IsTerminal,
     sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}},
-    time::Instant,
+    time::{Duration, Instant},
 };
 use std::str::FromStr;
+use serde_json::json;
 use rayon::prelude::*;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use walkdir::WalkDir;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 use log::{info, debug};
 use crate::{
     workspace::{Workspace, WorkspaceMember},
-    compiler::Compiler,
+    compiler::{Compiler, PrecompiledHeader},
+    ninja::{self, NinjaFile},
     cache::BuildCache,
     target::Target,
     toolchains::Toolchain,
     error::{ForgeError, ForgeResult},
-    config::TestConfig
+    messages::{self, millis, MessageFormat, Messages},
+    config::{BuildProfile, CacheMode, CleanMode, CompilerConfig, CompilerFlavor, Language, Lto, OutputType, Sanitizer, TestConfig, TestMode},
 };
 
+/// Globs over the paths of sources relative to the directory they were
+/// found in, for test `patterns` and the `exclude` lists. Patterns
+/// containing a `/` match the relative path, where `**` spans
+/// directories; the others match the file name in any directory.
+struct PathGlobs {
+    names: GlobSet,
+    paths: GlobSet,
+}
+
+impl PathGlobs {
+    /// `what` names the patterns in errors, e.g. `test pattern`.
+    fn new(patterns: &[String], what: &str) -> ForgeResult<Self> {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| ForgeError::Config(format!("Invalid {} '{}': {}", what, pattern, e)))?;
+            if pattern.contains('/') {
+                paths.add(glob);
+            } else {
+                names.add(glob);
+            }
+        }
+
+        let build = |set: GlobSetBuilder| set.build()
+            .map_err(|e| ForgeError::Config(format!("Invalid {}s: {}", what, e)));
+        Ok(PathGlobs { names: build(names)?, paths: build(paths)? })
+    }
+
+    fn is_match(&self, relative: &Path) -> bool {
+        relative.file_name().is_some_and(|name| self.names.is_match(name))
+            || self.paths.is_match(relative)
+    }
+
+    /// Whether `relative` or one of the directories it is in matches, so
+    /// that `experimental` or `experimental/*` leave out everything below.
+    fn matches_below(&self, relative: &Path) -> bool {
+        relative.ancestors()
+            .filter(|path| !path.as_os_str().is_empty())
+            .any(|path| self.is_match(path))
+    }
+}
+
+/// Files below `dir` in name order, or only those directly in it when not
+/// `recursive`.
+fn walk_files(dir: &Path, recursive: bool) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(dir)
+        .max_depth(if recursive { usize::MAX } else { 1 })
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+}
+
+/// The files named by `[paths] sources`, in the order listed. Globs are
+/// matched below their leading directories without wildcards, where `*`
+/// stays within a directory and `**` spans any number of them.
+fn explicit_sources(member: &WorkspaceMember, entries: &[String]) -> ForgeResult<Vec<PathBuf>> {
+    let mut sources = Vec::new();
+    for entry in entries {
+        let is_glob = |part: &str| part.contains(['*', '?', '[', '{']);
+        if !is_glob(entry) {
+            let path = member.resolve_path(entry);
+            if !path.is_file() {
+                return Err(ForgeError::FileNotFound(path));
+            }
+            if !sources.contains(&path) {
+                sources.push(path);
+            }
+            continue;
+        }
+
+        let parts: Vec<&str> = entry.split(['/', '\\']).collect();
+        let literal = parts.iter().take_while(|part| !is_glob(part)).count();
+        let base = member.resolve_path(&parts[..literal].join("/"));
+        let glob = GlobBuilder::new(&parts[literal..].join("/"))
+            .literal_separator(true)
+            .build()
+            .map_err(|e| ForgeError::Config(format!("Invalid source pattern '{}': {}", entry, e)))?
+            .compile_matcher();
+
+        let count = sources.len();
+        for path in walk_files(&base, true) {
+            let matches = path.strip_prefix(&base).is_ok_and(|relative| glob.is_match(relative));
+            if matches && !sources.contains(&path) {
+                sources.push(path);
+            }
+        }
+        if sources.len() == count {
+            messages::warning(format_args!("source pattern '{}' of {} matches no files", entry, member.name));
+        }
+    }
+    Ok(sources)
+}
+
+/// Threads compiling a member: `--jobs`, then the member's `jobs`, then
+/// the root's. `None` leaves rayon's default of one per CPU.
+fn resolved_jobs(cli: Option<usize>, member: Option<usize>, root: Option<usize>) -> Option<usize> {
+    cli.or(member).or(root)
+}
+
+/// Where the compiler writes the dependencies of `output` for ninja.
+fn depfile(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".d");
+    PathBuf::from(path)
+}
+
+/// The `build-info.json` next to the member's output.
+fn build_info_path(member: &WorkspaceMember) -> PathBuf {
+    member.get_output_dir().join("build-info.json")
+}
+
+/// Writes the generated test `main` into `test_build_dir`, leaving an
+/// unchanged file alone so it isn't recompiled on every run.
+fn write_test_main(test_build_dir: &Path, source: &str) -> ForgeResult<PathBuf> {
+    let path = test_build_dir.join("forge_test_main.cpp");
+    if std::fs::read_to_string(&path).ok().as_deref() != Some(source) {
+        std::fs::write(&path, source)
+            .map_err(|e| ForgeError::Build(format!("Failed to write {}: {}", path.display(), e)))?;
+    }
+    Ok(path)
+}
+
+/// The toolchain building `member`: for the target given on the command
+/// line, or else the member's `[cross]` section (whose toolchain and sysroot
+/// the command line may still override) or the workspace's `default_target`.
+/// Native builds need none, nor does a `[cross]` naming the host without a
+/// toolchain or sysroot. An empty toolchain or sysroot counts as unset.
+fn member_toolchain(
+    member: &WorkspaceMember,
+    toolchain_env: &BTreeMap<String, String>,
+    toolchain_path: Option<&str>,
+    sysroot: Option<&Path>,
+) -> ForgeResult<Option<Toolchain>> {
+    let cross = member.config.cross.as_ref().filter(|_| member.selected_target.is_none());
+    let Some(triple) = member.target_triple() else {
+        return Ok(None);
+    };
+    let target = Target::from_str(triple)?;
+
+    let toolchain_path = toolchain_path
+        .or(cross.and_then(|c| c.toolchain.as_deref()))
+        .filter(|path| !path.is_empty());
+    let sysroot = sysroot
+        .or(cross.and_then(|c| c.sysroot.as_deref()))
+        .filter(|path| !path.as_os_str().is_empty());
+    if cross.is_some() && toolchain_path.is_none() && sysroot.is_none() && target == Target::host()? {
+        return Ok(None);
+    }
+
+    Toolchain::new(
+        target,
+        toolchain_path,
+        sysroot,
+        cross.map(|c| c.extra_flags.clone()).unwrap_or_default(),
+    ).map(|toolchain| {
+        let mut env = toolchain_env.clone();
+        env.extend(cross.iter().flat_map(|c| c.env.clone()));
+        Some(toolchain.with_env(env))
+    })
+}
+
 pub struct Builder {
     workspace: Workspace,
-    compiler: Compiler,
+    /// One compiler per member, as members may target different platforms
+    compilers: HashMap<String, Compiler>,
     cache: Arc<Mutex<BuildCache>>,
-    target_triple: Option<String>,
     selected_profile: Option<String>,
     quick_check: bool,
+    warnings_as_errors: Option<bool>,
+    jobs: Option<usize>,
+    force: bool,
+    force_members: Vec<String>,
+    force_files: Vec<PathBuf>,
+    verbosity: u8,
+    messages: Messages,
+    timings: Option<Mutex<Timings>>,
+    size_report: bool,
+    no_link: bool,
+    keep_going: bool,
+    sanitizers: Option<Vec<Sanitizer>>,
+    progress_bars: MultiProgress,
+}
+
+/// How long each file and member took in one build, for `--timings`.
+#[derive(Default)]
+struct Timings {
+    files: Vec<(String, PathBuf, Duration)>,
+    members: Vec<(String, Duration)>,
+}
+
+/// What a `build` did, with its members in build order.
+#[derive(Debug, Clone, Default)]
+pub struct BuildReport {
+    pub members: Vec<MemberReport>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemberReport {
+    pub name: String,
+    /// Sources compiled in this build
+    pub compiled: usize,
+    /// Sources whose objects were up to date
+    pub cached: usize,
+    /// Whether the member was left out, for lacking the selected profile
+    pub skipped: bool,
+    pub duration: Duration,
+    /// Whether nothing was compiled and the output was already linked
+    pub up_to_date: bool,
+    /// The library or executable, unless nothing was linked
+    pub output_path: Option<PathBuf>,
 }
 
+impl BuildReport {
+    /// Sources compiled across all members.
+    pub fn compiled(&self) -> usize {
+        self.members.iter().map(|m| m.compiled).sum()
+    }
+
+    /// Sources across all members whose objects were up to date.
+    pub fn cached(&self) -> usize {
+        self.members.iter().map(|m| m.cached).sum()
+    }
+}
+
+/// How a member fared in a `--keep-going` build, for its summary.
+enum MemberOutcome<'a> {
+    /// Built, or skipped for lacking the profile when `true`
+    Built(&'a str, bool),
+    Failed(&'a str),
+    /// Not attempted, as the dependency named second failed
+    Blocked(&'a str, &'a str),
+}
+
+/// Files listed in the `--timings` summary.
+const SLOWEST_FILES: usize = 10;
+
 impl Builder {
     pub fn new(
         mut workspace: Workspace,
@@ -33,37 +274,59 @@ impl Builder {
         toolchain_path: Option<&str>,
         sysroot: Option<&Path>,
         profile: Option<&str>,
-    ) -> Self {
-        let mut cache = BuildCache::new(&workspace.root_path);
-        cache.set_quick_check(true);
-
-        let toolchain = target_triple.map(|triple| {
-            let target = Target::from_str(triple).expect("Invalid target triple");
-            Toolchain::new(
-                target,
-                toolchain_path,
-                sysroot,
-                vec![],
-            ).expect("Failed to create toolchain")
-        });
+    ) -> ForgeResult<Self> {
+        let mut cache = match &workspace.build_dir {
+            Some(dir) => BuildCache::with_dir(dir.join(".forge_cache")),
+            None => BuildCache::new(&workspace.root_path),
+        };
+        let quick_check = workspace.root_config.build.cache_mode == CacheMode::Quick;
+        cache.set_quick_check(quick_check);
 
         let selected_profile = profile.map(String::from);
         workspace.set_profile(selected_profile.clone());
-        Builder {
+        workspace.set_target(target_triple.map(String::from));
+        let progress_bars = MultiProgress::with_draw_target(ProgressDrawTarget::stdout());
+
+        let mut compilers = HashMap::new();
+        for member in &workspace.members {
+            let toolchain_env = &workspace.root_config.build.toolchain_env;
+            let toolchain = member_toolchain(member, toolchain_env, toolchain_path, sysroot)?;
+            let mut compiler = Compiler::new(toolchain);
+            compiler.set_progress_bars(progress_bars.clone());
+            compilers.insert(member.name.clone(), compiler);
+        }
+
+        Ok(Builder {
             workspace,
-            compiler: Compiler::new(toolchain),
+            compilers,
             cache: Arc::new(Mutex::new(cache)),
-            target_triple: target_triple.map(String::from),
             selected_profile,
-            quick_check: true,
-        }
+            quick_check,
+            warnings_as_errors: None,
+            jobs: None,
+            force: false,
+            force_members: Vec::new(),
+            force_files: Vec::new(),
+            verbosity: 0,
+            messages: Messages::default(),
+            timings: None,
+            size_report: false,
+            no_link: false,
+            keep_going: false,
+            sanitizers: None,
+            progress_bars,
+        })
+    }
+
+    fn compiler(&self, member: &WorkspaceMember) -> &Compiler {
+        &self.compilers[&member.name]
     }
 
     pub fn build_tests(&self, member: &WorkspaceMember, test_config: &TestConfig) -> ForgeResult<()> {
         let start = Instant::now();
         info!("\nBuilding tests for {}", member.name);
 
-        let test_build_dir = member.get_build_dir().join("tests");
+        let test_build_dir = member.get_test_dir();
         std::fs::create_dir_all(&test_build_dir)
             .map_err(|e| ForgeError::Build(format!("Failed to create test build directory: {}", e)))?;
 
@@ -73,7 +336,9 @@ impl Builder {
             return Ok(());
         }
         info!("Found {} test files", test_sources.len());
+        self.cache.lock().unwrap().load()?;
 
+        let test_count = test_sources.len();
         let mut all_sources = test_sources;
         if let Some(main) = &test_config.main {
             let main_path = member.path.join(main);
@@ -82,37 +347,42 @@ impl Builder {
             } else {
                 return Err(ForgeError::Build(format!("Test main file not found: {}", main)));
             }
+        } else if let Some(source) = test_config.framework.and_then(|f| f.main_source()) {
+            all_sources.push(write_test_main(&test_build_dir, source)?);
         }
 
-        let target = self.target_triple.as_deref()
-            .or_else(|| member.config.cross.as_ref().map(|c| c.target.as_str()))
-            .unwrap_or("native");
-
-        let profile = self.selected_profile.as_deref()
-            .unwrap_or(&member.config.build.default_profile);
+        let target = self.member_target(member);
+        let profile = self.member_profile(member);
 
-        let profile_config = member.config.get_profile(Some(profile))
+        let profile_config = &self.resolve_profile(member, profile)
             .ok_or_else(|| ForgeError::Build(format!("Profile not found: {}", profile)))?;
 
-        let mut compiler_flags = member.config.compiler.flags.clone();
-        compiler_flags.extend(profile_config.extra_flags.iter().cloned());
+        self.check_flags(member, profile, profile_config, &test_config.flags)?;
+
+        let compiler_config = self.compiler_config(member, profile_config);
+        let mut compiler_flags = self.cache_flags(member, &compiler_config, profile_config);
         compiler_flags.extend(test_config.flags.iter().cloned());
 
-        let total_files = all_sources.len();
-        let completed_files = Arc::new(AtomicUsize::new(0));
+        let dependencies = self.workspace.dependencies_of(member)?;
+        let include_dirs = self.include_dirs(member, &dependencies);
+
+        let progress = self.progress(&member.name, all_sources.len());
 
-        let objects: Vec<PathBuf> = all_sources.par_iter()
+        let objects: ForgeResult<Vec<PathBuf>> = all_sources.par_iter()
             .map(|source| {
-                let object = self.compiler.get_object_path(source, &test_build_dir);
-                let includes = self.compiler.get_includes(source, &member.get_include_dirs());
+                let object = self.compiler(member).get_object_path(&member.relative_source_path(source), &test_build_dir);
+                let includes = self.compiler(member).get_includes(source, &include_dirs);
 
-                let needs_rebuild = {
+                let compiler = self.source_compiler(member, source);
+
+                let needs_rebuild = self.is_forced(member, source) || {
                     let cache = self.cache.lock().unwrap();
                     cache.needs_rebuild(
                         source,
                         &object,
                         &includes,
                         &compiler_flags,
+                        &compiler,
                         target,
                         profile
                     )
@@ -120,57 +390,78 @@ impl Builder {
 
                 if !needs_rebuild {
                     debug!("Skipping {} (up to date)", source.display());
-                    let done = completed_files.fetch_add(1, Ordering::SeqCst) + 1;
-                    info!("Progress: [{}/{}]", done, total_files);
+                    self.report_compile(member, source, &object, None, &Ok(()));
+                    progress.completed();
                     return Ok(object);
                 }
 
-                debug!("Compiling {}", source.display());
-                let mut test_compiler_config = member.config.compiler.clone();
+                progress.compiling(source);
+                let mut test_compiler_config = compiler_config.clone();
                 test_compiler_config.flags.extend(test_config.flags.iter().cloned());
-                test_compiler_config.libraries.extend(test_config.libs.iter().cloned());
+                test_compiler_config.libraries.extend(test_config.libs());
 
-                self.compiler.compile(
+                let compile_start = Instant::now();
+                let compiled = self.compiler(member).compile(
                     source,
                     &object,
                     &test_compiler_config,
                     profile_config,
-                    &member.get_include_dirs(),
-                    &member.config.build.compiler,
-                )?;
+                    &include_dirs,
+                    &member.config.build,
+                    None,
+                );
+                self.report_compile(member, source, &object, Some(compile_start), &compiled);
+                compiled.map_err(|e| self.failure_context(member, "compile", source, e))?;
 
                 {
                     let mut cache = self.cache.lock().unwrap();
                     cache.update(
                         source,
+                        &object,
                         &includes,
                         &compiler_flags,
+                        &compiler,
                         target,
                         profile,
                     )?;
                 }
 
-                let done = completed_files.fetch_add(1, Ordering::SeqCst) + 1;
-                info!("Progress: [{}/{}]", done, total_files);
+                progress.completed();
                 Ok(object)
             })
-            .collect::<ForgeResult<_>>()?;
+            .collect();
+        progress.finish();
+        let objects = objects?;
 
-        if !objects.is_empty() {
-            let test_binary = member.get_build_dir().join("tests").join(&member.config.build.target);
+        let mut test_compiler_config = compiler_config.clone();
+        test_compiler_config.libraries.extend(test_config.libs());
+        let link = |objects: &[PathBuf], test_binary: &Path| {
             info!("Linking {}", test_binary.display());
-
-            let mut test_compiler_config = member.config.compiler.clone();
-            test_compiler_config.libraries.extend(test_config.libs.iter().cloned());
-
-            self.compiler.link(
-                &objects,
-                &test_binary,
+            self.compiler(member).link(
+                objects,
+                test_binary,
                 &test_compiler_config,
                 profile_config,
-                &member.config.build.compiler,
-            )?;
+                &member.config.build,
+                OutputType::Executable,
+            ).map_err(|e| self.failure_context(member, "link", test_binary, e))
+        };
+
+        match test_config.mode {
+            TestMode::Single => link(&objects, &member.get_test_binary_path())?,
+            TestMode::PerFile => {
+                let (test_objects, main_objects) = objects.split_at(test_count);
+                all_sources[..test_count].par_iter()
+                    .zip(test_objects)
+                    .map(|(source, object)| {
+                        let mut objects = vec![object.clone()];
+                        objects.extend(main_objects.iter().cloned());
+                        link(&objects, &member.get_test_source_binary_path(source))
+                    })
+                    .collect::<ForgeResult<()>>()?;
+            }
         }
+        self.cache.lock().unwrap().save()?;
 
         info!(
             "Built tests for {} in {:.2}s",
@@ -180,6 +471,18 @@ impl Builder {
         Ok(())
     }
 
+    /// The test executables `build_tests` produces for `member`: one, or
+    /// one per test source in per-file mode.
+    pub fn test_binaries(&self, member: &WorkspaceMember, test_config: &TestConfig) -> ForgeResult<Vec<PathBuf>> {
+        match test_config.mode {
+            TestMode::Single => Ok(vec![member.get_test_binary_path()]),
+            TestMode::PerFile => Ok(self.find_test_sources(member, test_config)?
+                .iter()
+                .map(|source| member.get_test_source_binary_path(source))
+                .collect()),
+        }
+    }
+
     fn find_test_sources(&self, member: &WorkspaceMember, test_config: &TestConfig) -> ForgeResult<Vec<PathBuf>> {
         let test_dir = if let Some(dir) = &test_config.test_dir {
             member.path.join(dir)
@@ -191,113 +494,295 @@ impl Builder {
             return Ok(Vec::new());
         }
 
-        fn matches_pattern(name: &str, pattern: &str) -> bool {
-            if pattern.starts_with("*") && pattern.ends_with("*") {
-                let inner = &pattern[1..pattern.len() - 1];
-                name.contains(inner)
-            } else if pattern.starts_with("*") {
-                let suffix = &pattern[1..];
-                name.ends_with(suffix)
-            } else if pattern.ends_with("*") {
-                let prefix = &pattern[..pattern.len() - 1];
-                name.starts_with(prefix)
-            } else {
-                name == pattern
-            }
-        }
+        let patterns = PathGlobs::new(&test_config.patterns(), "test pattern")?;
+        let exclude = PathGlobs::new(&test_config.exclude, "test exclude pattern")?;
 
-        let sources: Vec<_> = WalkDir::new(&test_dir)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| {
-                if let Some(file_name) = e.path().file_name().and_then(|n| n.to_str()) {
-                    /* if matches any */
-                    let matches = test_config.patterns.iter()
-                        .any(|p| matches_pattern(file_name, p));
-
-                    /* if excluded */
-                    let excluded = test_config.exclude.iter()
-                        .any(|p| matches_pattern(file_name, p));
-
-                    matches && !excluded
-                } else {
-                    false
-                }
+        let sources: Vec<_> = walk_files(&test_dir, member.config.paths.recursive)
+            .filter(|path| {
+                let relative = path.strip_prefix(&test_dir).unwrap_or(path);
+                patterns.is_match(relative)
+                    && !exclude.matches_below(relative)
+                    && member.is_source(path)
             })
-            .map(|e| e.path().to_path_buf())
             .collect();
 
         Ok(sources)
     }
 
-    pub fn build(&self, members: &[&WorkspaceMember]) -> ForgeResult<()> {
+    pub fn build(&self, members: &[&WorkspaceMember]) -> ForgeResult<BuildReport> {
         let start = Instant::now();
         info!("Starting build process");
 
+        let result = self.build_selected(members);
+        self.messages.event("build-finished", json!({
+            "success": result.is_ok(),
+            "duration_ms": millis(start.elapsed()),
+        }));
+
+        let mut report = result?;
+        report.duration = start.elapsed();
+        info!(
+            "Build completed in {:.2}s",
+            start.elapsed().as_secs_f32()
+        );
+        self.report_timings(start.elapsed())?;
+        Ok(report)
+    }
+
+    /// Prints the slowest files and every member's build time, and writes
+    /// all of them to `timings.json` in the build directory.
+    fn report_timings(&self, total: Duration) -> ForgeResult<()> {
+        let Some(timings) = &self.timings else {
+            return Ok(());
+        };
+        let Timings { mut files, mut members } = std::mem::take(&mut *timings.lock().unwrap());
+        files.sort_by_key(|(_, _, duration)| std::cmp::Reverse(*duration));
+        members.sort_by_key(|(_, duration)| std::cmp::Reverse(*duration));
+
+        if !files.is_empty() {
+            self.messages.status("Slowest files:");
+            for (member, file, duration) in files.iter().take(SLOWEST_FILES) {
+                let file = file.strip_prefix(&self.workspace.root_path).unwrap_or(file);
+                self.messages.status(format_args!(
+                    "  {:>8.2}s  {}  {}",
+                    duration.as_secs_f32(),
+                    member,
+                    file.display()
+                ));
+            }
+        }
+        self.messages.status("Members:");
+        for (member, duration) in &members {
+            self.messages.status(format_args!("  {:>8.2}s  {}", duration.as_secs_f32(), member));
+        }
+
+        let report = json!({
+            "total_ms": millis(total),
+            "members": members.iter()
+                .map(|(member, duration)| json!({ "member": member, "duration_ms": millis(*duration) }))
+                .collect::<Vec<_>>(),
+            "files": files.iter()
+                .map(|(member, file, duration)| json!({
+                    "member": member,
+                    "file": file,
+                    "duration_ms": millis(*duration),
+                }))
+                .collect::<Vec<_>>(),
+        });
+
+        let build_root = self.workspace.build_root();
+        std::fs::create_dir_all(&build_root)
+            .map_err(|e| ForgeError::Build(format!("Failed to create build directory: {}", e)))?;
+        let path = build_root.join("timings.json");
+        std::fs::write(&path, serde_json::to_string_pretty(&report)?)
+            .map_err(|e| ForgeError::Build(format!("Failed to write {}: {}", path.display(), e)))?;
+        self.messages.status(format_args!("Timings written to {}", path.display()));
+        Ok(())
+    }
+
+    /// `members` and their dependencies, grouped into levels that only
+    /// depend on earlier ones. Every member when `members` is empty.
+    fn build_levels(&self, members: &[&WorkspaceMember]) -> ForgeResult<Vec<Vec<&WorkspaceMember>>> {
+        let mut selected: Vec<&str> = members.iter().map(|m| m.name.as_str()).collect();
+        for member in members {
+            selected.extend(self.workspace.dependencies_of(member)?.iter().map(|d| d.name.as_str()));
+        }
+
+        Ok(self.workspace.get_build_levels()?
+            .into_iter()
+            .map(|level| level.into_iter()
+                .filter(|m| members.is_empty() || selected.contains(&m.name.as_str()))
+                .collect::<Vec<_>>())
+            .filter(|level| !level.is_empty())
+            .collect())
+    }
+
+    fn build_selected(&self, members: &[&WorkspaceMember]) -> ForgeResult<BuildReport> {
+        // The global pool can only be set up once; later builds in the same
+        // process (e.g. further profiles) keep using it.
+        if let Some(jobs) = resolved_jobs(self.jobs, None, self.workspace.root_config.build.jobs) {
+            let _ = rayon::ThreadPoolBuilder::new().num_threads(jobs).build_global();
+        }
+
         debug!("Loading build cache");
         self.cache.lock().unwrap().load()?;
 
-        let build_order = self.workspace.get_build_order()?;
-        let filtered: Vec<_> = build_order.into_iter()
-            .filter(|m| members.is_empty() || members.iter().any(|member| member.name == m.name))
-            .collect();
+        let levels = self.build_levels(members)?;
 
-        debug!("Build order: {:?}", filtered.iter().map(|m| &m.name).collect::<Vec<_>>());
+        // Once per member rather than as a failure for every source
+        for member in levels.iter().flatten() {
+            let build = &member.config.build;
+            if build.output_type == OutputType::Interface {
+                continue;
+            }
+            for language in [Language::Cxx, Language::C] {
+                self.compiler(member).verify_compiler(build.compiler_for(language), &member.name)?;
+            }
+        }
 
-        for member in filtered {
-            self.build_member(member)?;
+        // Members of a level don't depend on each other and share the global
+        // rayon pool with the per-file compilation inside build_member.
+        let build = |member: &&WorkspaceMember| {
+            let start = Instant::now();
+            let result = self.build_member(member);
+            if let Some(timings) = &self.timings {
+                timings.lock().unwrap().members.push((member.name.clone(), start.elapsed()));
+            }
+            self.messages.event("member-finished", json!({
+                "member": member.name,
+                "success": result.is_ok(),
+                "up_to_date": result.as_ref().is_ok_and(|r| r.up_to_date),
+                "duration_ms": millis(start.elapsed()),
+            }));
+            result
+        };
+
+        let mut report = BuildReport::default();
+        let mut outcomes = Vec::new();
+        // Members that failed or were skipped for it, in build order
+        let mut failed: Vec<&str> = Vec::new();
+        for level in levels {
+            debug!("Building level: {:?}", level.iter().map(|m| &m.name).collect::<Vec<_>>());
+            if !self.keep_going {
+                report.members.extend(level.par_iter().map(build).collect::<ForgeResult<Vec<_>>>()?);
+                continue;
+            }
+
+            let mut ready = Vec::new();
+            for member in level {
+                let blocked_by = self.workspace.dependencies_of(member)?
+                    .into_iter()
+                    .find(|dep| failed.contains(&dep.name.as_str()));
+                match blocked_by {
+                    Some(dep) => {
+                        self.messages.event("member-skipped", json!({
+                            "member": member.name,
+                            "failed_dependency": dep.name,
+                        }));
+                        outcomes.push(MemberOutcome::Blocked(&member.name, &dep.name));
+                        failed.push(&member.name);
+                    }
+                    None => ready.push(member),
+                }
+            }
+
+            let results: Vec<_> = ready.par_iter().map(build).collect();
+            for (member, result) in ready.iter().zip(results) {
+                match result {
+                    Ok(member_report) => {
+                        outcomes.push(MemberOutcome::Built(&member.name, member_report.skipped));
+                        report.members.push(member_report);
+                    }
+                    Err(e) => {
+                        self.messages.error(&format!("Building {} failed", member.name), &e);
+                        outcomes.push(MemberOutcome::Failed(&member.name));
+                        failed.push(&member.name);
+                    }
+                }
+            }
         }
 
         debug!("Saving build cache");
         self.cache.lock().unwrap().save()?;
 
-        info!(
-            "Build completed in {:.2}s",
-            start.elapsed().as_secs_f32()
-        );
-        Ok(())
+        if failed.is_empty() {
+            return Ok(report);
+        }
+
+        self.messages.status("Build summary:");
+        for outcome in &outcomes {
+            match outcome {
+                MemberOutcome::Built(name, false) => self.messages.status(format_args!("  built    {}", name)),
+                MemberOutcome::Built(name, true) => self.messages.status(format_args!("  skipped  {} (no such profile)", name)),
+                MemberOutcome::Failed(name) => self.messages.status(format_args!("  failed   {}", name)),
+                MemberOutcome::Blocked(name, dep) => self.messages.status(format_args!("  skipped  {} ({} failed)", name, dep)),
+            }
+        }
+
+        let failures: Vec<&str> = outcomes.iter()
+            .filter_map(|outcome| match outcome {
+                MemberOutcome::Failed(name) => Some(*name),
+                _ => None,
+            })
+            .collect();
+        Err(ForgeError::Build(format!(
+            "{} of {} members failed ({}), {} skipped",
+            failures.len(),
+            outcomes.len(),
+            failures.join(", "),
+            failed.len() - failures.len(),
+        )))
     }
 
-    fn build_member(&self, member: &WorkspaceMember) -> ForgeResult<()> {
+    fn build_member(&self, member: &WorkspaceMember) -> ForgeResult<MemberReport> {
         let start = Instant::now();
         info!("\nBuilding {}", member.name);
+        let mut report = MemberReport { name: member.name.clone(), ..MemberReport::default() };
 
         std::fs::create_dir_all(member.get_build_dir())
             .map_err(|e| ForgeError::Build(format!("Failed to create build directory: {}", e)))?;
 
+        let target = self.member_target(member);
+        let profile = self.member_profile(member);
+
+        let Some(ref profile_config) = self.resolve_profile(member, profile) else {
+            messages::warning(format_args!("profile {} not found for {}, skipping", profile, member.name));
+            report.skipped = true;
+            report.duration = start.elapsed();
+            return Ok(report);
+        };
+
+        self.messages.event("member-started", json!({
+            "member": member.name,
+            "profile": profile,
+            "target": target,
+        }));
+
+        let compiler_config = self.compiler_config(member, profile_config);
+        let compiler_flags = self.cache_flags(member, &compiler_config, profile_config);
+        self.check_flags(member, profile, profile_config, &[])?;
+
+        self.run_hooks(member, "pre-build", &member.config.hooks.pre_build, target, profile)?;
+        self.run_generators(member)?;
+
         let sources = self.find_sources(member)?;
         info!("Found {} source files", sources.len());
 
-        let target = self.target_triple.as_deref()
-            .or_else(|| member.config.cross.as_ref().map(|c| c.target.as_str()))
-            .unwrap_or("native");
+        let pruned = self.prune_member(member, &sources)?;
+        if !pruned.is_empty() {
+            info!("Removed {} objects of deleted or excluded sources", pruned.len());
+        }
 
-        let profile = self.selected_profile.as_deref()
-            .unwrap_or(&member.config.build.default_profile);
+        let dependencies = self.workspace.dependencies_of(member)?;
+        let include_dirs = self.include_dirs(member, &dependencies);
 
-        let profile_config = member.config.get_profile(Some(profile))
-            .ok_or_else(|| ForgeError::Build(format!("Profile not found: {}", profile)))?;
+        let pch = self.build_precompiled_header(
+            member,
+            &compiler_config,
+            profile_config,
+            &include_dirs,
+            &compiler_flags,
+        )?;
+        let pch_includes = self.pch_includes(member, &compiler_config, &include_dirs);
 
-        let compiler_flags: Vec<String> = member.config.compiler.flags.iter()
-            .chain(profile_config.extra_flags.iter())
-            .cloned()
-            .collect();
+        let progress = self.progress(&member.name, sources.len());
+        let compiled_count = AtomicUsize::new(0);
 
-        let total_files = sources.len();
-        let completed_files = Arc::new(AtomicUsize::new(0));
+        let compile_all = || {
+            let results = sources.par_iter().map(|source| {
+                let object = self.compiler(member).get_object_path(&member.relative_source_path(source), &member.get_object_dir());
+                let includes = self.source_includes(member, source, &include_dirs, &pch_includes);
 
-        let objects: Vec<PathBuf> = sources.par_iter()
-            .map(|source| {
-                let object = self.compiler.get_object_path(source, &member.get_build_dir());
-                let includes = self.compiler.get_includes(source, &member.get_include_dirs());
+                let compiler = self.source_compiler(member, source);
 
-                let needs_rebuild = {
+                let needs_rebuild = self.is_forced(member, source) || {
                     let cache = self.cache.lock().unwrap();
                     cache.needs_rebuild(
                         source,
                         &object,
                         &includes,
                         &compiler_flags,
+                        &compiler,
                         target,
                         profile
                     )
@@ -305,93 +790,1391 @@ impl Builder {
 
                 if !needs_rebuild {
                     debug!("Skipping {} (up to date)", source.display());
-                    let done = completed_files.fetch_add(1, Ordering::SeqCst) + 1;
-                    info!("Progress: [{}/{}]", done, total_files);
+                    self.report_compile(member, source, &object, None, &Ok(()));
+                    progress.completed();
                     return Ok(object);
                 }
 
-                debug!("Compiling {}", source.display());
-                self.compiler.compile(
+                progress.compiling(source);
+                let compile_start = Instant::now();
+                let compiled = self.compiler(member).compile(
                     source,
                     &object,
-                    &member.config.compiler,
+                    &compiler_config,
                     profile_config,
-                    &member.get_include_dirs(),
-                    &member.config.build.compiler,
-                )?;
+                    &include_dirs,
+                    &member.config.build,
+                    pch.as_ref(),
+                );
+                self.report_compile(member, source, &object, Some(compile_start), &compiled);
+                if let Err(e) = compiled {
+                    // A failed source is done too, so --keep-going's bar still fills
+                    progress.completed();
+                    return Err(self.failure_context(member, "compile", source, e));
+                }
+                compiled_count.fetch_add(1, Ordering::Relaxed);
 
                 {
                     let mut cache = self.cache.lock().unwrap();
                     cache.update(
                         source,
+                        &object,
                         &includes,
                         &compiler_flags,
+                        &compiler,
                         target,
                         profile,
                     )?;
                 }
 
-                let done = completed_files.fetch_add(1, Ordering::SeqCst) + 1;
-                info!("Progress: [{}/{}]", done, total_files);
+                progress.completed();
                 Ok(object)
-            })
-            .collect::<ForgeResult<_>>()?;
+            });
 
-        if !objects.is_empty() {
+            if self.keep_going {
+                all_compiled(results.collect())
+            } else {
+                results.collect::<ForgeResult<Vec<PathBuf>>>()
+            }
+        };
+
+        let objects = match self.member_jobs(member) {
+            Some(jobs) => rayon::ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build()
+                .map_err(|e| ForgeError::Build(format!("Failed to create thread pool: {}", e)))?
+                .install(compile_all),
+            None => compile_all(),
+        };
+        progress.finish();
+        let mut objects = objects?;
+        report.compiled = compiled_count.into_inner();
+        report.cached = sources.len() - report.compiled;
+        if let Some(object) = pch.as_ref().and_then(|pch| pch.object()) {
+            if !objects.is_empty() {
+                objects.push(object);
+            }
+        }
+
+        if self.no_link {
+            self.messages.status(format_args!(
+                "Compiled {} objects of {} into {} (not linked)",
+                objects.len(),
+                member.name,
+                member.get_object_dir().display()
+            ));
+            self.messages.event("objects", json!({
+                "member": member.name,
+                "objects": objects,
+            }));
+            if report.compiled > 0 || !build_info_path(member).exists() {
+                self.write_build_info(member, profile_config, &compiler_flags, &sources, None)?;
+            }
+            info!("Built {} in {:.2}s", member.name, start.elapsed().as_secs_f32());
+            report.duration = start.elapsed();
+            return Ok(report);
+        }
+
+        let link_config = self.link_config(&compiler_config, &dependencies)?;
+        let link_key = self.link_key(member, &objects, &link_config, profile_config);
+        let link_inputs = self.link_inputs(member, &objects, &dependencies, &link_config);
+        let output_type = member.config.build.output_type;
+        if !objects.is_empty() && self.link_up_to_date(member, &link_inputs, &link_key) {
+            debug!("Skipping link of {} (up to date)", member.get_target_path().display());
+            self.report_link(member, output_type, None);
+            report.up_to_date = report.compiled == 0;
+        } else if !objects.is_empty() {
+            let link_start = Instant::now();
             info!("Linking {}", member.get_target_path().display());
-            self.compiler.link(
-                &objects,
-                &member.get_target_path(),
-                &member.config.compiler,
-                profile_config,
-                &member.config.build.compiler,
-            )?;
+            let linked = if output_type == OutputType::Static {
+                self.compiler(member).archive(&objects, &member.get_target_path(), member.config.build.flavor())
+            } else {
+                self.compiler(member).link(
+                    &objects,
+                    &member.get_target_path(),
+                    &link_config,
+                    profile_config,
+                    &member.config.build,
+                    output_type,
+                )
+            };
+            linked.map_err(|e| self.failure_context(member, "link", &member.get_target_path(), e))?;
+
+            let build = &member.config.build;
+            let strips = output_type != OutputType::Static && self.compiler(member).strips(profile_config, build);
+            let required = &build.require_symbols;
+            if !required.is_empty() {
+                let missing = self.compiler(member).missing_symbols(&member.get_target_path(), required, strips)?;
+                if !missing.is_empty() {
+                    return Err(ForgeError::Build(format!(
+                        "{} is missing required symbols: {}",
+                        member.get_target_path().display(),
+                        missing.join(", ")
+                    )));
+                }
+            }
+
+            // MSVC links debug info into a separate .pdb on its own
+            let splits_debug = output_type != OutputType::Static
+                && member.config.build.flavor() == CompilerFlavor::Gnu;
+            if profile_config.split_debug && profile_config.debug_info && splits_debug {
+                debug!("Splitting debug info of {}", member.get_target_path().display());
+                self.compiler(member).split_debug_info(&member.get_target_path())?;
+            }
+
+            if strips && self.compiler(member).strips_after_link(profile_config, build) {
+                debug!("Stripping {}", member.get_target_path().display());
+                self.compiler(member).strip(&member.get_target_path(), build)?;
+            }
+
+            self.cache.lock().unwrap().update_link(&member.get_target_path(), &link_key, &link_inputs)?;
+            self.report_link(member, output_type, Some(link_start));
+        }
+
+        let linked = matches!(output_type, OutputType::Executable | OutputType::Shared);
+        if linked && !objects.is_empty() && !report.up_to_date && (self.size_report || member.config.build.emit_map) {
+            self.report_size(member)?;
+        }
+        if !objects.is_empty() {
+            report.output_path = Some(member.get_target_path());
+        }
+
+        // An up to date member's build info still describes it
+        if !report.up_to_date || !build_info_path(member).exists() {
+            let link_command = (!objects.is_empty()).then(|| match output_type {
+                OutputType::Static => self.compiler(member).archive_command(&objects, &member.get_target_path(), member.config.build.flavor()),
+                _ => self.compiler(member).link_command(
+                    &objects,
+                    &member.get_target_path(),
+                    &link_config,
+                    profile_config,
+                    &member.config.build,
+                    output_type,
+                ),
+            });
+            self.write_build_info(member, profile_config, &compiler_flags, &sources, link_command.as_ref())?;
+        }
+
+        // Nothing was linked for the hooks to act on
+        if report.up_to_date {
+            info!("{} is up to date", member.name);
+            report.duration = start.elapsed();
+            return Ok(report);
         }
 
+        self.run_hooks(member, "post-build", &member.config.hooks.post_build, target, profile)?;
+
         info!(
             "Built {} in {:.2}s",
             member.name,
             start.elapsed().as_secs_f32()
         );
+        report.duration = start.elapsed();
+        Ok(report)
+    }
+
+    /// Writes a `build.ninja` to `path` that builds `members` and their
+    /// dependencies with the commands `build` would run. Hooks, required
+    /// symbol checks and split debug info are left to `forge build`.
+    pub fn write_ninja(&self, members: &[&WorkspaceMember], path: &Path) -> ForgeResult<()> {
+        let mut ninja = NinjaFile::new();
+        let mut defaults = Vec::new();
+
+        for member in self.build_levels(members)?.into_iter().flatten() {
+            let profile = self.member_profile(member);
+            let profile_config = &self.resolve_profile(member, profile)
+                .ok_or_else(|| ForgeError::Build(format!("Profile not found: {}", profile)))?;
+            self.check_flags(member, profile, profile_config, &[])?;
+
+            let compiler = self.compiler(member);
+            let build = &member.config.build;
+            let compiler_config = self.compiler_config(member, profile_config);
+            let dependencies = self.workspace.dependencies_of(member)?;
+            let include_dirs = self.include_dirs(member, &dependencies);
+            ninja.comment(&format!("{} ({}, {})", member.name, profile, self.member_target(member)));
+
+            let mut sources = self.find_sources(member)?;
+            let mut generated = Vec::new();
+            for rule in &member.config.generate {
+                let outputs: Vec<PathBuf> = rule.outputs.iter().map(|o| member.resolve_path(o)).collect();
+                let inputs: Vec<PathBuf> = rule.inputs.iter().map(|i| member.resolve_path(i)).collect();
+                ninja.build(&outputs, "generate", &inputs, &[], &ninja::shell_in(&member.path, &rule.command));
+
+                // Generated sources may not exist yet
+                for output in outputs {
+                    if member.is_source(&output) && !sources.contains(&output) {
+                        sources.push(output.clone());
+                    }
+                    generated.push(output);
+                }
+            }
+
+            let mut pch_outputs = Vec::new();
+            let mut pch = None;
+            if let Some(header) = &compiler_config.precompiled_header {
+                let header = std::path::absolute(member.resolve_path(header))
+                    .map_err(|e| ForgeError::Build(format!("Failed to resolve precompiled header: {}", e)))?;
+                let precompiled = compiler.precompiled_header(&header, &member.get_output_dir().join("pch"), build);
+                let mut cmd = compiler.precompile_command(&precompiled, &compiler_config, profile_config, &include_dirs, build)
+                    .ok_or_else(|| ForgeError::Build(format!(
+                        "{}: precompiled headers are not supported in ninja files with MSVC",
+                        member.name
+                    )))?;
+                cmd.arg("-MMD").arg("-MF").arg(depfile(&precompiled.output));
+                ninja.build(std::slice::from_ref(&precompiled.output), "cc", &[header], &generated, &ninja::command_line(&cmd));
+                pch_outputs.push(precompiled.output.clone());
+                pch = Some(precompiled);
+            }
+
+            let mut objects = Vec::new();
+            for source in &sources {
+                let object = compiler.get_object_path(&member.relative_source_path(source), &member.get_object_dir());
+                let mut cmd = compiler.compile_command(
+                    source,
+                    &object,
+                    &compiler_config,
+                    profile_config,
+                    &include_dirs,
+                    build,
+                    pch.as_ref(),
+                );
+                let rule = match build.flavor() {
+                    CompilerFlavor::Gnu => {
+                        cmd.arg("-MMD").arg("-MF").arg(depfile(&object));
+                        "cc"
+                    }
+                    CompilerFlavor::Msvc => {
+                        cmd.arg("/showIncludes");
+                        "cc_msvc"
+                    }
+                };
+                let implicit: Vec<PathBuf> = pch_outputs.iter()
+                    .chain(&generated)
+                    .filter(|path| *path != source)
+                    .cloned()
+                    .collect();
+                ninja.build(std::slice::from_ref(&object), rule, std::slice::from_ref(source), &implicit, &ninja::command_line(&cmd));
+                objects.push(object);
+            }
+
+            if objects.is_empty() {
+                continue;
+            }
+
+            let target = member.get_target_path();
+            let link_config = self.link_config(&compiler_config, &dependencies)?;
+            let (rule, cmd) = if build.output_type == OutputType::Static {
+                let cmd = ninja::command_line(&compiler.archive_command(&objects, &target, build.flavor()));
+                match build.flavor() {
+                    // `lib` replaces its output, `ar` adds to it
+                    CompilerFlavor::Gnu => ("archive", ninja::replacing(&target, cmd)),
+                    CompilerFlavor::Msvc => ("archive", cmd),
+                }
+            } else {
+                let cmd = compiler.link_command(&objects, &target, &link_config, profile_config, build, build.output_type);
+                ("link", ninja::command_line(&cmd))
+            };
+            let libraries: Vec<PathBuf> = self.link_inputs(member, &objects, &dependencies, &link_config)
+                .into_iter()
+                .filter(|input| !objects.contains(input))
+                .collect();
+            ninja.build(std::slice::from_ref(&target), rule, &objects, &libraries, &cmd);
+            defaults.push(target);
+        }
+
+        ninja.default(&defaults);
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| ForgeError::Build(format!("Failed to create {}: {}", parent.display(), e)))?;
+        }
+        std::fs::write(path, ninja.finish())
+            .map_err(|e| ForgeError::Build(format!("Failed to write {}: {}", path.display(), e)))?;
         Ok(())
     }
 
-    fn find_sources(&self, member: &WorkspaceMember) -> ForgeResult<Vec<PathBuf>> {
-        let src_dir = member.get_source_dir();
-        if !src_dir.exists() {
-            return Ok(Vec::new());
+    fn run_generators(&self, member: &WorkspaceMember) -> ForgeResult<()> {
+        for rule in &member.config.generate {
+            let key = format!("{}/{}", member.name, rule.name);
+            let inputs: Vec<PathBuf> = rule.inputs.iter()
+                .map(|input| member.resolve_path(input))
+                .collect();
+
+            if let Some(missing) = inputs.iter().find(|input| !input.exists()) {
+                return Err(ForgeError::Build(format!(
+                    "Input {} of generator {} not found",
+                    missing.display(),
+                    rule.name
+                )));
+            }
+
+            let outputs_exist = rule.outputs.iter()
+                .all(|output| member.resolve_path(output).exists());
+
+            let needs_rerun = !outputs_exist || self.force || self.force_members.contains(&member.name) || {
+                let cache = self.cache.lock().unwrap();
+                cache.generator_needs_rerun(&key, &rule.command, &inputs)
+            };
+
+            if !needs_rerun {
+                debug!("Skipping generator {} (up to date)", rule.name);
+                continue;
+            }
+
+            info!("Running generator {}", rule.name);
+            let status = shell_command(&rule.command)
+                .current_dir(&member.path)
+                .status()
+                .map_err(|e| ForgeError::Build(format!("Failed to run generator {}: {}", rule.name, e)))?;
+
+            if !status.success() {
+                return Err(ForgeError::Build(format!(
+                    "Generator {} exited with code {}",
+                    rule.name,
+                    status.code().unwrap_or(-1)
+                )));
+            }
+
+            self.cache.lock().unwrap().update_generator(&key, &rule.command, &inputs)?;
         }
 
-        let sources: Vec<_> = WalkDir::new(&src_dir)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| {
-                e.path()
-                    .extension()
-                    .map_or(false, |ext| ext == "cpp" || ext == "c" || ext == "cc")
+        Ok(())
+    }
+
+    /// Runs the member's `pre_build` or `post_build` commands in its
+    /// directory, describing the build through `FORGE_*` variables.
+    fn run_hooks(
+        &self,
+        member: &WorkspaceMember,
+        stage: &str,
+        commands: &[String],
+        target: &str,
+        profile: &str,
+    ) -> ForgeResult<()> {
+        // Hooks run in the member directory, so relative paths would be off
+        let build_dir = std::path::absolute(member.get_output_dir())
+            .map_err(|e| ForgeError::Build(format!("Failed to resolve build directory: {}", e)))?;
+
+        for command in commands {
+            info!("Running {} hook: {}", stage, command);
+            let status = shell_command(command)
+                .current_dir(&member.path)
+                .env("FORGE_MEMBER", &member.name)
+                .env("FORGE_BUILD_DIR", &build_dir)
+                .env("FORGE_TARGET", target)
+                .env("FORGE_PROFILE", profile)
+                .status()
+                .map_err(|e| ForgeError::Build(format!("Failed to run {} hook of {}: {}", stage, member.name, e)))?;
+
+            if !status.success() {
+                return Err(ForgeError::Build(format!(
+                    "{} hook of {} exited with code {}: {}",
+                    stage,
+                    member.name,
+                    status.code().unwrap_or(-1),
+                    command
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A `compiled` message for `source`, or `compiler-error` carrying the
+    /// compiler's output when it failed. `start` is `None` for sources that
+    /// were up to date.
+    fn report_compile(
+        &self,
+        member: &WorkspaceMember,
+        source: &Path,
+        object: &Path,
+        start: Option<Instant>,
+        result: &ForgeResult<()>,
+    ) {
+        if let (Some(timings), Some(start), Ok(())) = (&self.timings, start, result) {
+            timings.lock().unwrap().files.push((member.name.clone(), source.to_path_buf(), start.elapsed()));
+        }
+
+        match result {
+            Ok(()) => self.messages.event("compiled", json!({
+                "member": member.name,
+                "file": source,
+                "object": object,
+                "fresh": start.is_none(),
+                "duration_ms": start.map_or(0, |start| millis(start.elapsed())),
+            })),
+            Err(e) => {
+                let stderr = match e {
+                    ForgeError::Compiler(stderr) => stderr.clone(),
+                    e => e.to_string(),
+                };
+                self.messages.event("compiler-error", json!({
+                    "member": member.name,
+                    "file": source,
+                    "stderr": stderr,
+                }));
+            }
+        }
+    }
+
+    /// Says which member, file and configuration a compiler or linker
+    /// failed on, since several of them may be running at once.
+    fn failure_context(&self, member: &WorkspaceMember, action: &str, path: &Path, error: ForgeError) -> ForgeError {
+        let ForgeError::Compiler(output) = error else {
+            return error;
+        };
+
+        let separator = if output.trim_end().contains('\n') { "\n" } else { " " };
+        ForgeError::Compiler(format!(
+            "{}: failed to {} {} (profile {}, target {}):{}{}",
+            member.name,
+            action,
+            path.display(),
+            self.member_profile(member),
+            self.member_target(member),
+            separator,
+            output.trim_end()
+        ))
+    }
+
+    /// Writes `build-info.json` next to the member's output, recording the
+    /// compilers, flags, sources and link command of this build so that
+    /// builds in different environments can be compared. Paths of sources
+    /// and the output are relative to the workspace root.
+    fn write_build_info(
+        &self,
+        member: &WorkspaceMember,
+        profile_config: &BuildProfile,
+        compiler_flags: &[String],
+        sources: &[PathBuf],
+        link_command: Option<&Command>,
+    ) -> ForgeResult<()> {
+        let build = &member.config.build;
+        let relative = |path: &Path| path.strip_prefix(&self.workspace.root_path).unwrap_or(path).to_path_buf();
+
+        let compiler = |language| {
+            let command = build.compiler_for(language);
+            json!({
+                "command": command,
+                "path": self.compiler(member).compiler_path(command),
+                "version": self.compiler(member).version(command, build.flavor()),
             })
-            .map(|e| e.path().to_path_buf())
+        };
+
+        let sources = sources.iter()
+            .map(|source| Ok(json!({
+                "path": relative(source),
+                "sha256": BuildCache::hash_file(source)?,
+            })))
+            .collect::<ForgeResult<Vec<_>>>()?;
+
+        let link_command = link_command.map(|cmd| std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect::<Vec<_>>());
+
+        let info = json!({
+            "version": 1,
+            "member": member.name,
+            "profile": self.member_profile(member),
+            "profile_settings": profile_config,
+            "target": self.member_target(member),
+            "output_type": build.output_type,
+            "output": link_command.is_some().then(|| relative(&member.get_target_path())),
+            "compilers": {
+                "c": compiler(Language::C),
+                "cxx": compiler(Language::Cxx),
+            },
+            "compile_flags": compiler_flags,
+            "link_command": link_command,
+            "sources": sources,
+        });
+
+        std::fs::create_dir_all(member.get_output_dir())
+            .map_err(|e| ForgeError::Build(format!("Failed to create output directory: {}", e)))?;
+        let path = build_info_path(member);
+        std::fs::write(&path, serde_json::to_string_pretty(&info)?)
+            .map_err(|e| ForgeError::Build(format!("Failed to write {}: {}", path.display(), e)))
+    }
+
+    /// Prints the section sizes of the member's output, e.g. `text 1234,
+    /// data 56, bss 78`.
+    fn report_size(&self, member: &WorkspaceMember) -> ForgeResult<()> {
+        let target = member.get_target_path();
+        if member.config.build.flavor() == CompilerFlavor::Msvc {
+            messages::warning(format_args!("size reports are not supported with MSVC, skipping {}", target.display()));
+            return Ok(());
+        }
+
+        let sizes = self.compiler(member).section_sizes(&target)?;
+        let summary: Vec<String> = sizes.iter().map(|(section, bytes)| format!("{} {}", section, bytes)).collect();
+        self.messages.status(format_args!("Size of {}: {}", target.display(), summary.join(", ")));
+        self.messages.event("size", json!({
+            "member": member.name,
+            "target": target,
+            "sections": sizes.into_iter().map(|(section, bytes)| (section, bytes.into())).collect::<serde_json::Map<_, _>>(),
+        }));
+        Ok(())
+    }
+
+    fn report_link(&self, member: &WorkspaceMember, output_type: OutputType, start: Option<Instant>) {
+        self.messages.event("linked", json!({
+            "member": member.name,
+            "target": member.get_target_path(),
+            "output_type": output_type,
+            "fresh": start.is_none(),
+            "duration_ms": start.map_or(0, |start| millis(start.elapsed())),
+        }));
+    }
+
+    /// A progress bar for compiling `total` files of a member, shown below
+    /// those of members building in parallel. Without a terminal, when
+    /// commands are echoed, or when stdout carries JSON messages, every
+    /// file gets a line instead; quiet builds get neither.
+    fn progress(&self, member: &str, total: usize) -> Progress {
+        let messages = self.messages;
+        if !std::io::stdout().is_terminal() || self.verbosity > 0 || !messages.shows_tool_output() {
+            return Progress { bar: None, messages, completed: AtomicUsize::new(0) };
+        }
+
+        let bar = self.progress_bars.add(ProgressBar::new(total as u64));
+        bar.set_style(ProgressStyle::with_template("{prefix:>12} [{bar:25}] {pos}/{len} {wide_msg}")
+            .expect("valid progress template")
+            .progress_chars("=> "));
+        bar.set_prefix(member.to_string());
+        Progress { bar: Some(bar), messages, completed: AtomicUsize::new(0) }
+    }
+
+    /// Looks up a profile in the member's own config first, then in the
+    /// workspace root config, with `--sanitize` replacing its sanitizers.
+    fn resolve_profile(&self, member: &WorkspaceMember, name: &str) -> Option<BuildProfile> {
+        let mut profile = member.config.get_profile(Some(name))
+            .or_else(|| self.workspace.root_config.get_profile(Some(name)))?
+            .clone();
+        if let Some(sanitizers) = &self.sanitizers {
+            profile.sanitizers = sanitizers.clone();
+        }
+        Some(profile)
+    }
+
+    fn member_target<'a>(&'a self, member: &'a WorkspaceMember) -> &'a str {
+        member.target_triple().unwrap_or("native")
+    }
+
+    fn member_profile<'a>(&'a self, member: &'a WorkspaceMember) -> &'a str {
+        self.selected_profile.as_deref()
+            .unwrap_or(&member.config.build.default_profile)
+    }
+
+    fn cache_flags(&self, member: &WorkspaceMember, compiler_config: &CompilerConfig, profile_config: &BuildProfile) -> Vec<String> {
+        let mut flags: Vec<String> = compiler_config.flags.iter()
+            .chain(profile_config.extra_flags.iter())
+            .cloned()
             .collect();
+        flags.extend(Sanitizer::flags(&profile_config.sanitizers, member.config.build.flavor()));
+        let clang = member.config.build.compiler_for(Language::Cxx).contains("clang");
+        flags.extend(profile_config.lto.compile_flags(member.config.build.flavor(), clang));
+        flags.extend(compiler_config.definition_flags("-D"));
+        flags.extend(compiler_config.system_include_paths.iter().map(|dir| format!("-isystem {}", dir)));
+        flags.extend(compiler_config.warning_flags(member.config.build.flavor()));
+        for language in [Language::C, Language::Cxx] {
+            if let Some(standard) = compiler_config.standard(language) {
+                flags.push(format!("-std={}", standard));
+            }
+        }
+        flags.extend(self.compiler(member).codegen_flags(compiler_config).iter().map(|f| f.to_string()));
+        flags.extend(self.compiler(member).toolchain_flags());
+        if let Some(header) = &compiler_config.precompiled_header {
+            flags.push(format!("-include {}", header));
+        }
+        flags
+    }
+
+    /// Identity of the compiler building `source` (see
+    /// `Compiler::identity`), recorded with its cache entry.
+    fn source_compiler(&self, member: &WorkspaceMember, source: &Path) -> String {
+        let build = &member.config.build;
+        self.compiler(member).identity(build.compiler_for(Language::of(source)), build.flavor())
+    }
+
+    /// Precompiles the member's `precompiled_header` into its output
+    /// directory, unless it is up to date.
+    fn build_precompiled_header(
+        &self,
+        member: &WorkspaceMember,
+        compiler_config: &CompilerConfig,
+        profile_config: &BuildProfile,
+        include_dirs: &[PathBuf],
+        compiler_flags: &[String],
+    ) -> ForgeResult<Option<PrecompiledHeader>> {
+        let Some(header) = &compiler_config.precompiled_header else {
+            return Ok(None);
+        };
+
+        let header = member.resolve_path(header);
+        if !header.exists() {
+            return Err(ForgeError::FileNotFound(header));
+        }
+        // MSVC matches the header by the name it was created with, and the
+        // stub source including it lives in the build directory
+        let header = std::path::absolute(&header)
+            .map_err(|e| ForgeError::Build(format!("Failed to resolve precompiled header: {}", e)))?;
+
+        let pch = self.compiler(member).precompiled_header(
+            &header,
+            &member.get_output_dir().join("pch"),
+            &member.config.build,
+        );
+
+        let target = self.member_target(member);
+        let profile = self.member_profile(member);
+        let includes = self.compiler(member).get_includes(&header, include_dirs);
+        let compiler = self.source_compiler(member, &header);
+
+        let needs_rebuild = self.is_forced(member, &header) || {
+            let cache = self.cache.lock().unwrap();
+            cache.needs_rebuild(&header, &pch.output, &includes, compiler_flags, &compiler, target, profile)
+        };
+
+        if needs_rebuild {
+            self.compiler(member).precompile_header(&pch, compiler_config, profile_config, include_dirs, &member.config.build)
+                .map_err(|e| self.failure_context(member, "precompile", &header, e))?;
+            self.cache.lock().unwrap()
+                .update(&header, &pch.output, &includes, compiler_flags, &compiler, target, profile)?;
+        } else {
+            debug!("Skipping {} (up to date)", header.display());
+        }
+
+        Ok(Some(pch))
+    }
+
+    /// The precompiled header and the files it includes, which every C++
+    /// source of the member depends on.
+    fn pch_includes(&self, member: &WorkspaceMember, compiler_config: &CompilerConfig, include_dirs: &[PathBuf]) -> Vec<PathBuf> {
+        let Some(header) = &compiler_config.precompiled_header else {
+            return Vec::new();
+        };
+
+        let header = member.resolve_path(header);
+        let mut includes = self.compiler(member).get_includes(&header, include_dirs);
+        includes.insert(0, header);
+        includes
+    }
+
+    fn source_includes(&self, member: &WorkspaceMember, source: &Path, include_dirs: &[PathBuf], pch_includes: &[PathBuf]) -> Vec<PathBuf> {
+        let mut includes = self.compiler(member).get_includes(source, include_dirs);
+        if Language::of(source) == Language::Cxx {
+            includes.extend(pch_includes.iter().cloned());
+        }
+        includes
+    }
+
+    pub fn explain_cache(&self, file: &Path) -> ForgeResult<String> {
+        self.cache.lock().unwrap().load()?;
+
+        let file = file.canonicalize()
+            .map_err(|_| ForgeError::FileNotFound(file.to_path_buf()))?;
+
+        for member in &self.workspace.members {
+            let sources = self.find_sources(member)?;
+            let Some(source) = sources.iter()
+                .find(|s| s.canonicalize().is_ok_and(|s| s == file)) else {
+                continue;
+            };
+
+            let profile = self.member_profile(member);
+            let profile_config = &self.resolve_profile(member, profile)
+                .ok_or_else(|| ForgeError::Build(format!("Profile not found: {}", profile)))?;
+            let compiler_config = self.compiler_config(member, profile_config);
+            let compiler_flags = self.cache_flags(member, &compiler_config, profile_config);
+
+            let dependencies = self.workspace.dependencies_of(member)?;
+            let include_dirs = self.include_dirs(member, &dependencies);
+            let object = self.compiler(member).get_object_path(&member.relative_source_path(source), &member.get_object_dir());
+            let pch_includes = self.pch_includes(member, &compiler_config, &include_dirs);
+            let includes = self.source_includes(member, source, &include_dirs, &pch_includes);
+
+            let compiler = self.source_compiler(member, source);
+
+            let cache = self.cache.lock().unwrap();
+            return cache.explain(
+                source,
+                &object,
+                &includes,
+                &compiler_flags,
+                &compiler,
+                self.member_target(member),
+                profile,
+            );
+        }
+
+        Err(ForgeError::Build(format!(
+            "{} is not a source file of any workspace member",
+            file.display()
+        )))
+    }
+
+    /// Rejects sanitizer sets the compiler can't build, then enforces the
+    /// workspace root's `[security]` flag allowlist on every
+    /// user-supplied flag list that ends up on a compile or link line.
+    fn check_flags(
+        &self,
+        member: &WorkspaceMember,
+        profile: &str,
+        profile_config: &BuildProfile,
+        test_flags: &[String],
+    ) -> ForgeResult<()> {
+        Sanitizer::check(
+            &format!("sanitizers of {}", member.name),
+            &profile_config.sanitizers,
+            member.config.build.flavor(),
+        )?;
+
+        let build = &member.config.build;
+        if profile_config.lto == Lto::Thin
+            && build.flavor() == CompilerFlavor::Gnu
+            && !build.linker_driver().contains("clang")
+        {
+            messages::warning(format_args!(
+                "profile {} of {} asks for thin LTO, which GCC lacks; using full LTO",
+                profile,
+                member.name
+            ));
+        }
+
+        let Some(security) = &self.workspace.root_config.security else {
+            return Ok(());
+        };
+
+        // Merged with the root's and the profile's settings, so none of
+        // them gets past the allowlist
+        let config = self.compiler_config(member, profile_config);
+        security.check_flags(&format!("[compiler] flags of {}", member.name), &config.flags)?;
+        for (setting, args) in self.compiler(member).configured_args(&config, build) {
+            security.check_flags(&format!("{} of {}", setting, member.name), &args)?;
+        }
+        security.check_flags(&format!("profile {} of {}", profile, member.name), &profile_config.extra_flags)?;
+        if let Some(cross) = &member.config.cross {
+            security.check_flags(&format!("[cross] extra_flags of {}", member.name), &cross.extra_flags)?;
+        }
+        security.check_flags(&format!("[testing] flags of {}", member.name), test_flags)?;
+
+        Ok(())
+    }
+
+    /// The member's include directories followed by the public ones of its
+    /// workspace dependencies, direct or not.
+    fn include_dirs(&self, member: &WorkspaceMember, dependencies: &[&WorkspaceMember]) -> Vec<PathBuf> {
+        let mut dirs = member.get_include_dirs();
+        for dir in dependencies.iter().flat_map(|dep| dep.get_public_include_dirs()) {
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        dirs
+    }
+
+    /// Puts the libraries produced by workspace dependencies on the link
+    /// line ahead of the member's own. Static and interface dependencies
+    /// also bring their own external libraries along.
+    fn link_config(&self, config: &CompilerConfig, dependencies: &[&WorkspaceMember]) -> ForgeResult<CompilerConfig> {
+        let mut link_config = config.clone();
+        let mut libraries = Vec::new();
+        let mut transitive = Vec::new();
+
+        for dep in dependencies {
+            let output_type = dep.config.build.output_type;
+            if output_type == OutputType::Executable {
+                continue;
+            }
+
+            if output_type != OutputType::Interface {
+                let dir = dep.get_output_dir().display().to_string();
+                if output_type == OutputType::Shared {
+                    // A relative rpath would resolve against the working
+                    // directory at runtime
+                    let rpath = std::path::absolute(dep.get_output_dir())
+                        .map_err(|e| ForgeError::Build(format!("Failed to resolve library directory: {}", e)))?;
+                    link_config.rpath.push(rpath.display().to_string());
+                }
+                link_config.library_paths.push(dir);
+                libraries.push(dep.config.build.target.clone());
+            }
+
+            if matches!(output_type, OutputType::Static | OutputType::Interface) {
+                link_config.library_paths.extend(dep.config.compiler.library_paths.iter().cloned());
+                transitive.extend(dep.config.compiler.libraries.iter().cloned());
+            }
+        }
+
+        libraries.append(&mut link_config.libraries);
+        libraries.extend(transitive);
+        link_config.libraries = libraries;
+        Ok(link_config)
+    }
+
+    /// Everything besides the objects' contents that goes into linking a
+    /// member. Library paths only count as a set, like on the link line.
+    fn link_key(
+        &self,
+        member: &WorkspaceMember,
+        objects: &[PathBuf],
+        link_config: &CompilerConfig,
+        profile_config: &BuildProfile,
+    ) -> String {
+        let build = &member.config.build;
+        let mut library_paths = link_config.unique_library_paths();
+        library_paths.sort();
+
+        format!(
+            "{:?} {} {:?} {:?} {:?} {:?} {:?} {} {:?} {:?} {} {} {} {:?} {:?} {:?}",
+            build.output_type,
+            build.linker_driver(),
+            build.linker,
+            objects,
+            link_config.libraries,
+            library_paths,
+            link_config.rpath,
+            link_config.pie,
+            profile_config.lto,
+            profile_config.lto_jobs,
+            profile_config.split_debug && profile_config.debug_info,
+            build.emit_map,
+            self.compiler(member).strips(profile_config, build),
+            profile_config.sanitizers,
+            profile_config.extra_flags,
+            self.compiler(member).toolchain_flags(),
+        )
+    }
+
+    /// The files a member's output is linked from: its objects, the
+    /// libraries of its dependencies, and those of its `libraries` that are
+    /// found in its `library_paths`. Libraries the linker finds on its own
+    /// search path are not tracked.
+    fn link_inputs(
+        &self,
+        member: &WorkspaceMember,
+        objects: &[PathBuf],
+        dependencies: &[&WorkspaceMember],
+        link_config: &CompilerConfig,
+    ) -> Vec<PathBuf> {
+        let mut inputs = objects.to_vec();
+        // Archiving doesn't read any libraries
+        if member.config.build.output_type == OutputType::Static {
+            return inputs;
+        }
+
+        let dependency_libraries = dependencies.iter()
+            .filter(|dep| matches!(dep.config.build.output_type, OutputType::Static | OutputType::Shared))
+            .map(|dep| dep.get_target_path());
+        let library_paths = link_config.unique_library_paths();
+        let flavor = member.config.build.flavor();
+        let external_libraries = link_config.libraries.iter()
+            .filter_map(|library| find_library(library, &library_paths, flavor));
+
+        for library in dependency_libraries.chain(external_libraries) {
+            if !inputs.contains(&library) {
+                inputs.push(library);
+            }
+        }
+        inputs
+    }
+
+    /// Whether the member's output can be kept: it is newer than all of
+    /// `inputs`, and was linked from the same, unchanged inputs with the
+    /// same configuration.
+    fn link_up_to_date(&self, member: &WorkspaceMember, inputs: &[PathBuf], link_key: &str) -> bool {
+        if self.force || self.force_members.contains(&member.name) {
+            return false;
+        }
+
+        let target = member.get_target_path();
+        let Some(linked) = modified(&target) else {
+            return false;
+        };
+
+        let inputs_older = inputs.iter()
+            .all(|input| modified(input).is_some_and(|time| time <= linked));
+
+        inputs_older && !self.cache.lock().unwrap().link_needs_rerun(&target, link_key, inputs)
+    }
+
+    /// A member's own `jobs` setting, when it should get a dedicated pool:
+    /// `--jobs` overrides it, and a value equal to the root's is already
+    /// what the global pool uses.
+    fn member_jobs(&self, member: &WorkspaceMember) -> Option<usize> {
+        let root = self.workspace.root_config.build.jobs;
+        let global = resolved_jobs(self.jobs, None, root);
+        resolved_jobs(self.jobs, member.config.build.jobs, root)
+            .filter(|jobs| Some(*jobs) != global)
+    }
+
+    fn is_forced(&self, member: &WorkspaceMember, source: &Path) -> bool {
+        if self.force || self.force_members.contains(&member.name) {
+            return true;
+        }
+
+        !self.force_files.is_empty() && source.canonicalize()
+            .is_ok_and(|source| self.force_files.contains(&source))
+    }
+
+    /// The member's `[compiler]` section merged with the workspace root's
+    /// and the profile's. Flags, definitions and library paths go root,
+    /// member, profile; libraries go the other way round, as the more
+    /// specific ones need to come first on the link line. The root's
+    /// warnings come before the member's. Of several `-std=`
+    /// flags for a language only the last one is kept.
+    fn compiler_config(&self, member: &WorkspaceMember, profile_config: &BuildProfile) -> CompilerConfig {
+        let mut config = member.config.compiler.clone();
+        // Relative to the member, or to the root for the root's entries
+        config.system_include_paths = config.system_include_paths.iter()
+            .map(|dir| member.resolve_path(dir).display().to_string())
+            .collect();
+
+        if member.path != self.workspace.root_path {
+            let root = &self.workspace.root_config.compiler;
+            config.system_include_paths = root.system_include_paths.iter()
+                .map(|dir| self.workspace.root_path.join(dir).display().to_string())
+                .chain(config.system_include_paths)
+                .collect();
+            config.flags = root.flags.iter().chain(&config.flags).cloned().collect();
+            config.definitions = root.definitions.clone().into_iter().chain(config.definitions).collect();
+            config.library_paths = root.library_paths.iter().chain(&config.library_paths).cloned().collect();
+            config.libraries.extend(root.libraries.iter().cloned());
+            config.rpath = root.rpath.iter().chain(&config.rpath).cloned().collect();
+            for (warnings, root_warnings) in [
+                (&mut config.warnings, &root.warnings),
+                (&mut config.warnings_allowed, &root.warnings_allowed),
+            ] {
+                let own = std::mem::take(warnings);
+                for warning in root_warnings.iter().chain(&own) {
+                    if !warnings.contains(warning) {
+                        warnings.push(warning.clone());
+                    }
+                }
+            }
+            config.warnings_as_errors |= root.warnings_as_errors;
+            config.pic |= root.pic;
+            config.pie |= root.pie;
+            config.response_file |= root.response_file;
+        }
+
+        let overrides = &profile_config.compiler;
+        config.flags.extend(overrides.flags.iter().cloned());
+        config.definitions.extend(overrides.definitions.clone());
+        config.library_paths.extend(overrides.library_paths.iter().cloned());
+        config.libraries = overrides.libraries.iter().chain(&config.libraries).cloned().collect();
+
+        for language in [Language::C, Language::Cxx] {
+            if let Some(last) = config.flags.iter().rposition(|f| std_flag_language(f) == Some(language)) {
+                let mut index = 0;
+                config.flags.retain(|f| {
+                    index += 1;
+                    index - 1 == last || std_flag_language(f) != Some(language)
+                });
+            }
+        }
+
+        if let Some(enable) = self.warnings_as_errors {
+            config.warnings_as_errors = enable;
+        }
+        if member.config.build.output_type == OutputType::Shared {
+            config.pic = true;
+        }
+        config
+    }
+
+    /// The member's `[paths] sources` if set, else the files with a source
+    /// extension in its source directories that `[paths] exclude` doesn't
+    /// match.
+    fn find_sources(&self, member: &WorkspaceMember) -> ForgeResult<Vec<PathBuf>> {
+        let mut sources = Vec::new();
+        if member.config.build.output_type == OutputType::Interface {
+            return Ok(sources);
+        }
+
+        if let Some(entries) = &member.config.paths.sources {
+            return explicit_sources(member, entries);
+        }
+
+        let exclude = PathGlobs::new(&member.config.paths.exclude, "exclude pattern")?;
+        let build_dir = member.get_build_dir();
+        for src_dir in member.get_source_dirs() {
+            if !src_dir.exists() {
+                continue;
+            }
+
+            // Generated files in a build directory below the sources aren't
+            // sources of their own
+            sources.extend(walk_files(&src_dir, member.config.paths.recursive)
+                .filter(|path| member.is_source(path) && !path.starts_with(&build_dir))
+                .filter(|path| !exclude.matches_below(path.strip_prefix(&src_dir).unwrap_or(path))));
+        }
 
         Ok(sources)
     }
 
-    pub fn clean(&self, members: &[&WorkspaceMember]) -> ForgeResult<()> {
-        info!("Cleaning workspace");
+    /// Removes the objects and cache entries of deleted or excluded sources
+    /// from the build directories of `members`.
+    pub fn prune(&self, members: &[&WorkspaceMember]) -> ForgeResult<()> {
+        self.cache.lock().unwrap().load()?;
+
         for member in members {
-            member.clean()?;
+            let sources = self.find_sources(member)?;
+            let pruned = self.prune_member(member, &sources)?;
+            self.messages.status(format_args!("Pruned {} objects of {}", pruned.len(), member.name));
+            self.messages.event("pruned", json!({
+                "member": member.name,
+                "objects": pruned,
+            }));
         }
 
-        self.cache.lock().unwrap().clean()?;
+        self.cache.lock().unwrap().save()
+    }
+
+    /// Removes the objects of the member's deleted sources, and those of
+    /// the current profile and target that `sources` no longer lists.
+    fn prune_member(&self, member: &WorkspaceMember, sources: &[PathBuf]) -> ForgeResult<Vec<PathBuf>> {
+        let mut cache = self.cache.lock().unwrap();
+        let mut pruned = cache.prune(&member.get_build_dir())?;
+        pruned.extend(cache.prune_unlisted(&member.get_object_dir(), sources)?);
+        Ok(pruned)
+    }
+
+    /// Removes the build outputs and/or cache entries of `members`. The
+    /// cache directory is only removed as a whole when every member is
+    /// cleaned; otherwise just the entries of the given members are.
+    pub fn clean(&self, members: &[&WorkspaceMember], mode: CleanMode) -> ForgeResult<()> {
+        info!("Cleaning workspace");
+        if mode != CleanMode::Cache {
+            for member in members {
+                member.clean()?;
+            }
+        }
+
+        if mode != CleanMode::Artifacts {
+            let mut cache = self.cache.lock().unwrap();
+            if members.len() == self.workspace.members.len() {
+                cache.clean()?;
+            } else {
+                cache.load()?;
+                let dirs: Vec<PathBuf> = members.iter().map(|m| m.get_build_dir()).collect();
+                let names: Vec<&str> = members.iter().map(|m| m.name.as_str()).collect();
+                cache.clean_members(&dirs, &names)?;
+            }
+        }
 
         info!("Cleaned workspace");
         Ok(())
     }
 
+    pub fn set_profile(&mut self, profile: Option<String>) {
+        self.workspace.set_profile(profile.clone());
+        self.selected_profile = profile;
+    }
+
+    pub fn set_cache_dir(&mut self, dir: &Path) {
+        let mut cache = BuildCache::with_dir(dir.to_path_buf());
+        cache.set_quick_check(self.quick_check);
+        self.cache = Arc::new(Mutex::new(cache));
+    }
+
+    /// Bypasses the cache for everything (`all`), or only for the given
+    /// members and source files. Rebuilt items still update the cache.
+    pub fn set_force(&mut self, all: bool, members: Vec<String>, files: Vec<PathBuf>) {
+        self.force = all;
+        self.force_members = members;
+        self.force_files = files.into_iter()
+            .map(|file| file.canonicalize().unwrap_or(file))
+            .collect();
+    }
+
+    pub fn set_verbosity(&mut self, verbosity: u8) {
+        self.verbosity = verbosity;
+        for compiler in self.compilers.values_mut() {
+            compiler.set_verbosity(verbosity);
+        }
+    }
+
+    pub fn set_timings(&mut self, enable: bool) {
+        self.timings = enable.then(|| Mutex::new(Timings::default()));
+    }
+
+    /// Strips linked outputs (`Some(true)`) or keeps their symbols
+    /// (`Some(false)`) whatever the profile says.
+    pub fn set_strip(&mut self, strip: Option<bool>) {
+        for compiler in self.compilers.values_mut() {
+            compiler.set_strip(strip);
+        }
+    }
+
+    /// Builds with these sanitizers instead of the profiles' `sanitizers`.
+    pub fn set_sanitizers(&mut self, sanitizers: Option<Vec<Sanitizer>>) {
+        self.sanitizers = sanitizers;
+    }
+
+    /// Compiles without linking or archiving anything, and without the
+    /// post-build hooks that would act on the outputs.
+    pub fn set_no_link(&mut self, no_link: bool) {
+        self.no_link = no_link;
+    }
+
+    /// Compiles every source and builds every member whose dependencies
+    /// built after a failure, instead of stopping at the first one.
+    pub fn set_keep_going(&mut self, keep_going: bool) {
+        self.keep_going = keep_going;
+    }
+
+    /// Reports the section sizes of linked outputs even without
+    /// `emit_map`.
+    pub fn set_size_report(&mut self, enable: bool) {
+        self.size_report = enable;
+    }
+
+    pub fn set_message_format(&mut self, format: MessageFormat, quiet: bool) {
+        self.messages = Messages::new(format).quiet(quiet);
+        for compiler in self.compilers.values_mut() {
+            compiler.set_messages(self.messages);
+        }
+    }
+
+    pub fn set_jobs(&mut self, jobs: Option<usize>) {
+        self.jobs = jobs;
+    }
+
+    pub fn set_warnings_as_errors(&mut self, enable: Option<bool>) {
+        self.warnings_as_errors = enable;
+    }
+
     pub fn set_quick_check(&mut self, enable: bool) {
         self.quick_check = enable;
         if let Ok(mut cache) = self.cache.lock() {
             cache.set_quick_check(enable);
         }
     }
-}
\ No newline at end of file
+}
+
+/// The objects of a `--keep-going` compile, or the errors of every source
+/// that failed to compile, reported together.
+fn all_compiled(results: Vec<ForgeResult<PathBuf>>) -> ForgeResult<Vec<PathBuf>> {
+    let mut objects = Vec::new();
+    let mut failures = Vec::new();
+    for result in results {
+        match result {
+            Ok(object) => objects.push(object),
+            Err(ForgeError::Compiler(output)) => failures.push(output),
+            Err(e) => return Err(e),
+        }
+    }
+
+    match failures.len() {
+        0 => Ok(objects),
+        1 => Err(ForgeError::Compiler(failures.remove(0))),
+        count => Err(ForgeError::Compiler(format!(
+            "{} sources failed to compile:\n{}",
+            count,
+            failures.join("\n")
+        ))),
+    }
+}
+
+/// Compile progress of one member, updated from the rayon workers.
+struct Progress {
+    bar: Option<ProgressBar>,
+    messages: Messages,
+    completed: AtomicUsize,
+}
+
+impl Progress {
+    fn compiling(&self, source: &Path) {
+        match &self.bar {
+            Some(bar) => bar.set_message(source.display().to_string()),
+            None => self.messages.status(format_args!("Compiling {}", source.display())),
+        }
+    }
+
+    fn completed(&self) {
+        let done = self.completed.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some(bar) = &self.bar {
+            bar.set_position(done as u64);
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+fn shell_command(command: &str) -> Command {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command);
+    cmd
+}
+
+fn modified(path: &Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The file the linker picks for `-l<library>` (or `<library>.lib` with
+/// MSVC) from `library_paths`, searched in order.
+fn find_library(library: &str, library_paths: &[&str], flavor: CompilerFlavor) -> Option<PathBuf> {
+    let names = match flavor {
+        CompilerFlavor::Msvc if library.ends_with(".lib") => vec![library.to_string()],
+        CompilerFlavor::Msvc => vec![format!("{}.lib", library)],
+        CompilerFlavor::Gnu => match library.strip_prefix(':') {
+            Some(file) => vec![file.to_string()],
+            None => ["so", "dylib", "a", "lib"].iter()
+                .map(|ext| format!("lib{}.{}", library, ext))
+                .chain(std::iter::once(format!("{}.lib", library)))
+                .collect(),
+        },
+    };
+
+    library_paths.iter()
+        .flat_map(|dir| names.iter().map(move |name| Path::new(dir).join(name)))
+        .find(|path| path.is_file())
+}
+
+fn std_flag_language(flag: &str) -> Option<Language> {
+    let standard = flag.strip_prefix("-std=").or_else(|| flag.strip_prefix("/std:"))?;
+    if standard.contains("++") {
+        Some(Language::Cxx)
+    } else {
+        Some(Language::C)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jobs_precedence() {
+        assert_eq!(resolved_jobs(Some(4), Some(3), Some(2)), Some(4));
+        assert_eq!(resolved_jobs(Some(4), None, None), Some(4));
+        assert_eq!(resolved_jobs(None, Some(3), Some(2)), Some(3));
+        assert_eq!(resolved_jobs(None, None, Some(2)), Some(2));
+        assert_eq!(resolved_jobs(None, None, None), None);
+    }
+
+    fn toolchain_for(cross: &str) -> Option<Toolchain> {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("forge.toml"),
+            format!("[build]\ncompiler = \"g++\"\ntarget = \"app\"\n\n{}\n[paths]\n\n[compiler]\n", cross),
+        ).unwrap();
+        let workspace = Workspace::new(dir.path()).unwrap();
+        member_toolchain(&workspace.members[0], &BTreeMap::new(), None, None).unwrap()
+    }
+
+    #[test]
+    fn host_cross_target_builds_natively() {
+        let host = Target::host().unwrap();
+        assert!(toolchain_for("").is_none());
+        assert!(toolchain_for(&format!("[cross]\ntarget = \"{}\"\n", host)).is_none());
+        assert!(toolchain_for(&format!("[cross]\ntarget = \"{}\"\ntoolchain = \"\"\nsysroot = \"\"\n", host)).is_none());
+    }
+
+    #[test]
+    fn empty_cross_paths_are_unset() {
+        let toolchain = toolchain_for("[cross]\ntarget = \"aarch64-unknown-linux-gnu\"\ntoolchain = \"\"\nsysroot = \"\"\n").unwrap();
+        assert!(toolchain.get_sysroot().is_none());
+        assert!(toolchain.get_tool_path("ar").is_absolute());
+    }
+
+    fn globs(patterns: &[&str]) -> PathGlobs {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        PathGlobs::new(&patterns, "test pattern").unwrap()
+    }
+
+    #[test]
+    fn test_patterns_match_nested_directories() {
+        let recursive = globs(&["tests/**/*_test.cpp"]);
+        for path in ["tests/a_test.cpp", "tests/unit/a_test.cpp", "tests/unit/net/a_test.cpp"] {
+            assert!(recursive.is_match(Path::new(path)), "{}", path);
+        }
+        assert!(!recursive.is_match(Path::new("src/unit/a_test.cpp")));
+        assert!(!recursive.is_match(Path::new("tests/unit/a_test.hpp")));
+
+        let one_level = globs(&["unit/*_test.cpp"]);
+        assert!(one_level.is_match(Path::new("unit/a_test.cpp")));
+        assert!(!one_level.is_match(Path::new("unit/net/a_test.cpp")));
+
+        // Without a `/`, the file name is matched in any directory
+        let names = globs(&["foo_*_test.cpp", "[ab]_test.c"]);
+        assert!(names.is_match(Path::new("deep/er/foo_bar_test.cpp")));
+        assert!(names.is_match(Path::new("unit/b_test.c")));
+        assert!(!names.is_match(Path::new("unit/c_test.c")));
+        assert!(!names.is_match(Path::new("foo_test.cpp")));
+    }
+
+    #[test]
+    fn test_excludes_cover_nested_directories() {
+        let exclude = globs(&["experimental", "**/slow/*"]);
+        assert!(exclude.matches_below(Path::new("experimental/net/a_test.cpp")));
+        assert!(exclude.matches_below(Path::new("unit/slow/a_test.cpp")));
+        assert!(exclude.matches_below(Path::new("unit/net/slow/a_test.cpp")));
+        assert!(!exclude.matches_below(Path::new("unit/net/a_test.cpp")));
+    }
+
+    #[test]
+    fn test_sources_skip_excluded_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(
+            root.join("forge.toml"),
+            "[build]\ncompiler = \"g++\"\ntarget = \"app\"\n\n[paths]\n\n[compiler]\n\n\
+             [testing]\ntest_dir = \"tests\"\npatterns = [\"**/*_test.cpp\"]\nexclude = [\"experimental\"]\n",
+        ).unwrap();
+        for test in ["unit/a_test.cpp", "experimental/b_test.cpp", "unit/experimental/net/c_test.cpp"] {
+            let path = root.join("tests").join(test);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let workspace = Workspace::new(root).unwrap();
+        let builder = Builder::new(workspace.clone(), None, None, None, None).unwrap();
+        let member = &workspace.members[0];
+        let sources = builder.find_test_sources(member, member.config.testing.as_ref().unwrap()).unwrap();
+        assert_eq!(sources, [root.join("tests/unit/a_test.cpp")]);
+    }
+
+    #[test]
+    fn link_key_ignores_library_path_order() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("forge.toml"),
+            "[build]\ncompiler = \"g++\"\ntarget = \"app\"\n\n[paths]\n\n[compiler]\n",
+        ).unwrap();
+        let workspace = Workspace::new(dir.path()).unwrap();
+        let builder = Builder::new(workspace.clone(), None, None, None, None).unwrap();
+        let member = &workspace.members[0];
+        let profile = &member.config.profiles["debug"];
+        let key = |paths: &[&str], libraries: &[&str]| {
+            let mut config = member.config.compiler.clone();
+            config.library_paths = paths.iter().map(|p| p.to_string()).collect();
+            config.libraries = libraries.iter().map(|l| l.to_string()).collect();
+            builder.link_key(member, &[], &config, profile)
+        };
+
+        let base = key(&["/opt/a", "/opt/b"], &["net", "z"]);
+        assert_eq!(base, key(&["/opt/b", "/opt/a", "/opt/b"], &["net", "z"]));
+        assert_ne!(base, key(&["/opt/a", "/opt/b"], &["z", "net"]));
+    }
+
+    #[test]
+    fn invalid_test_pattern_is_a_config_error() {
+        let patterns = vec!["[unclosed".to_string()];
+        assert!(matches!(PathGlobs::new(&patterns, "test pattern"), Err(ForgeError::Config(_))));
+    }
+}
@@ -1,24 +1,85 @@
 use std::{
     collections::HashMap,
+    fmt,
     path::{Path, PathBuf},
     fs,
+    io::Write,
     time::{SystemTime, UNIX_EPOCH},
 };
 use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
-use log::{debug, trace};
+use log::debug;
+use rayon::prelude::*;
 use crate::error::{ForgeError, ForgeResult};
 
+const ENTRIES_FILE: &str = "entries.json";
+const GENERATORS_FILE: &str = "generators.json";
+const LINKS_FILE: &str = "links.json";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebuildReason {
+    ObjectMissing,
+    NoCacheEntry,
+    TargetChanged,
+    ProfileChanged,
+    CompilerChanged,
+    FlagsChanged,
+    SourceChanged,
+    IncludeChanged(PathBuf),
+    NewInclude(PathBuf),
+    IncludesChanged,
+}
+
+impl fmt::Display for RebuildReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RebuildReason::ObjectMissing => write!(f, "Object file doesn't exist"),
+            RebuildReason::NoCacheEntry => write!(f, "No cache entry found"),
+            RebuildReason::TargetChanged => write!(f, "Target changed"),
+            RebuildReason::ProfileChanged => write!(f, "Profile changed"),
+            RebuildReason::CompilerChanged => write!(f, "Compiler or compiler version changed"),
+            RebuildReason::FlagsChanged => write!(f, "Compiler flags changed"),
+            RebuildReason::SourceChanged => write!(f, "Source file changed"),
+            RebuildReason::IncludeChanged(path) => write!(f, "Include file {:?} changed", path),
+            RebuildReason::NewInclude(path) => write!(f, "New include file {:?}", path),
+            RebuildReason::IncludesChanged => write!(f, "Number of includes changed"),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CacheEntry {
-    hash: String,
+    object: PathBuf,
+    #[serde(default)]
+    source_path: PathBuf,
+    source: FileInfo,
     includes: HashMap<PathBuf, FileInfo>,
     compiler_flags: Vec<String>,
+    /// The compiler program and a hash of its version output
+    #[serde(default)]
+    compiler: String,
     target: String,
     profile: String,
     timestamp: u64,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GeneratorEntry {
+    command: String,
+    inputs: HashMap<PathBuf, FileInfo>,
+    timestamp: u64,
+}
+
+/// The link configuration a target was last linked with, hashed, and
+/// the files (objects and libraries) it was linked from.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinkEntry {
+    inputs_hash: String,
+    #[serde(default)]
+    inputs: HashMap<PathBuf, FileInfo>,
+    timestamp: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileInfo {
     hash: String,
@@ -29,79 +90,193 @@ pub struct FileInfo {
 pub struct BuildCache {
     cache_dir: PathBuf,
     entries: HashMap<PathBuf, CacheEntry>,
+    generators: HashMap<String, GeneratorEntry>,
+    links: HashMap<PathBuf, LinkEntry>,
+    /// Which tables changed since they were loaded or saved; only those
+    /// are written back
+    entries_dirty: bool,
+    generators_dirty: bool,
+    links_dirty: bool,
+    /// Per-object entry files of older versions, merged into the entries
+    /// table and removed once it has been saved
+    legacy_files: Vec<PathBuf>,
     quick_check: bool,
 }
 
 impl BuildCache {
     pub fn new(workspace_root: &Path) -> Self {
-        let cache_dir = workspace_root.join(".forge_cache");
-        fs::create_dir_all(&cache_dir).ok();
+        Self::with_dir(workspace_root.join(".forge_cache"))
+    }
 
+    pub fn with_dir(cache_dir: PathBuf) -> Self {
         BuildCache {
             cache_dir,
             entries: HashMap::new(),
+            generators: HashMap::new(),
+            links: HashMap::new(),
+            entries_dirty: false,
+            generators_dirty: false,
+            links_dirty: false,
+            legacy_files: Vec::new(),
             quick_check: true,
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn needs_rebuild(
         &self,
         source: &Path,
         object: &Path,
         includes: &[PathBuf],
         compiler_flags: &[String],
+        compiler: &str,
         target: &str,
         profile: &str,
     ) -> bool {
         debug!("Checking if {:?} needs rebuild...", source);
 
+        match self.rebuild_reason(source, object, includes, compiler_flags, compiler, target, profile) {
+            Some(reason) => {
+                debug!("{}", reason);
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn rebuild_reason(
+        &self,
+        source: &Path,
+        object: &Path,
+        includes: &[PathBuf],
+        compiler_flags: &[String],
+        compiler: &str,
+        target: &str,
+        profile: &str,
+    ) -> Option<RebuildReason> {
         if !object.exists() {
-            debug!("Object file doesn't exist");
-            return true;
+            return Some(RebuildReason::ObjectMissing);
         }
 
-        if let Some(entry) = self.entries.get(source) {
-            if entry.target != target ||
-                entry.profile != profile ||
-                entry.compiler_flags != compiler_flags {
-                debug!("Build configuration changed");
-                return true;
-            }
+        let Some(entry) = self.entries.get(object) else {
+            return Some(RebuildReason::NoCacheEntry);
+        };
 
-            if self.file_changed(source, &entry.hash) {
-                debug!("Source file changed");
-                return true;
-            }
+        if entry.target != target {
+            return Some(RebuildReason::TargetChanged);
+        }
 
-            for include in includes {
-                if let Some(info) = entry.includes.get(include) {
-                    if self.file_changed_with_info(include, info) {
-                        debug!("Include file {:?} changed", include);
-                        return true;
-                    }
-                } else {
-                    debug!("New include file {:?}", include);
-                    return true;
+        if entry.profile != profile {
+            return Some(RebuildReason::ProfileChanged);
+        }
+
+        if entry.compiler != compiler {
+            return Some(RebuildReason::CompilerChanged);
+        }
+
+        if entry.compiler_flags != compiler_flags {
+            return Some(RebuildReason::FlagsChanged);
+        }
+
+        if self.file_changed_with_info(source, &entry.source) {
+            return Some(RebuildReason::SourceChanged);
+        }
+
+        for include in includes {
+            if let Some(info) = entry.includes.get(include) {
+                if self.file_changed_with_info(include, info) {
+                    return Some(RebuildReason::IncludeChanged(include.clone()));
                 }
+            } else {
+                return Some(RebuildReason::NewInclude(include.clone()));
             }
+        }
 
-            if entry.includes.len() != includes.len() {
-                debug!("Number of includes changed");
-                return true;
-            }
+        if entry.includes.len() != includes.len() {
+            return Some(RebuildReason::IncludesChanged);
+        }
 
-            false
-        } else {
-            debug!("No cache entry found");
-            true
+        None
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn explain(
+        &self,
+        source: &Path,
+        object: &Path,
+        includes: &[PathBuf],
+        compiler_flags: &[String],
+        compiler: &str,
+        target: &str,
+        profile: &str,
+    ) -> ForgeResult<String> {
+        let mut out = String::new();
+        let reason = self.rebuild_reason(source, object, includes, compiler_flags, compiler, target, profile);
+
+        out.push_str(&format!("Cache decision for {}\n", source.display()));
+        out.push_str(&format!(
+            "  object:   {} ({})\n",
+            object.display(),
+            if object.exists() { "exists" } else { "missing" }
+        ));
+
+        let Some(entry) = self.entries.get(object) else {
+            out.push_str(&format!("  target:   {}\n", target));
+            out.push_str(&format!("  profile:  {}\n", profile));
+            out.push_str(&format!("  compiler: {}\n", compiler));
+            out.push_str(&format!("  flags:    {:?}\n", compiler_flags));
+            out.push_str(&format!("  decision: rebuild ({})\n", RebuildReason::NoCacheEntry));
+            return Ok(out);
+        };
+
+        out.push_str(&format!("  target:   stored {}, current {}\n", entry.target, target));
+        out.push_str(&format!("  profile:  stored {}, current {}\n", entry.profile, profile));
+        out.push_str(&format!("  compiler: stored {}, current {}\n", entry.compiler, compiler));
+        out.push_str(&format!("  flags:    stored {:?}\n", entry.compiler_flags));
+        out.push_str(&format!("            current {:?}\n", compiler_flags));
+
+        match self.get_file_info(source) {
+            Ok(info) => out.push_str(&format!(
+                "  source:   stored hash {}, mtime {}, size {}; current hash {}, mtime {}, size {}\n",
+                entry.source.hash, entry.source.mtime, entry.source.size, info.hash, info.mtime, info.size
+            )),
+            Err(e) => out.push_str(&format!("  source:   unreadable ({})\n", e)),
+        }
+
+        out.push_str("  includes:\n");
+        for include in includes {
+            let status = match entry.includes.get(include) {
+                Some(info) if self.file_changed_with_info(include, info) => "changed",
+                Some(_) => "unchanged",
+                None => "new",
+            };
+            out.push_str(&format!("    {} ({})\n", include.display(), status));
+        }
+        for include in entry.includes.keys().filter(|i| !includes.contains(i)) {
+            out.push_str(&format!("    {} (removed)\n", include.display()));
         }
+
+        match &reason {
+            Some(reason) => out.push_str(&format!("  decision: rebuild ({})\n", reason)),
+            None => out.push_str("  decision: up to date\n"),
+        }
+
+        let dump = serde_json::to_string_pretty(entry)
+            .map_err(|e| ForgeError::Cache(format!("Failed to serialize cache: {}", e)))?;
+        out.push_str(&format!("  entry:    {}\n", dump.replace('\n', "\n  ")));
+
+        Ok(out)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn update(
         &mut self,
         source: &Path,
+        object: &Path,
         includes: &[PathBuf],
         compiler_flags: &[String],
+        compiler: &str,
         target: &str,
         profile: &str,
     ) -> ForgeResult<()> {
@@ -115,11 +290,14 @@ impl BuildCache {
         }
 
         self.entries.insert(
-            source.to_path_buf(),
+            object.to_path_buf(),
             CacheEntry {
-                hash: self.get_file_info(source)?.hash,
+                object: object.to_path_buf(),
+                source_path: source.to_path_buf(),
+                source: self.get_file_info(source)?,
                 includes: include_infos,
                 compiler_flags: compiler_flags.to_vec(),
+                compiler: compiler.to_string(),
                 target: target.to_string(),
                 profile: profile.to_string(),
                 timestamp: SystemTime::now()
@@ -128,10 +306,126 @@ impl BuildCache {
                     .as_secs(),
             },
         );
+        self.entries_dirty = true;
+
+        Ok(())
+    }
+
+    pub fn generator_needs_rerun(&self, rule: &str, command: &str, inputs: &[PathBuf]) -> bool {
+        debug!("Checking if generator {} needs to run...", rule);
+
+        let Some(entry) = self.generators.get(rule) else {
+            debug!("No cache entry found");
+            return true;
+        };
+
+        if entry.command != command {
+            debug!("Generator command changed");
+            return true;
+        }
+
+        if entry.inputs.len() != inputs.len() {
+            debug!("Number of generator inputs changed");
+            return true;
+        }
+
+        for input in inputs {
+            match entry.inputs.get(input) {
+                Some(info) if !self.file_changed_with_info(input, info) => {}
+                Some(_) => {
+                    debug!("Generator input {:?} changed", input);
+                    return true;
+                }
+                None => {
+                    debug!("New generator input {:?}", input);
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    pub fn update_generator(&mut self, rule: &str, command: &str, inputs: &[PathBuf]) -> ForgeResult<()> {
+        let mut input_infos = HashMap::new();
+        for input in inputs {
+            input_infos.insert(input.to_path_buf(), self.get_file_info(input)?);
+        }
+
+        self.generators.insert(
+            rule.to_string(),
+            GeneratorEntry {
+                command: command.to_string(),
+                inputs: input_infos,
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+            },
+        );
+        self.generators_dirty = true;
 
         Ok(())
     }
 
+    /// Whether `target` was last linked with a different configuration,
+    /// described by `config`, or from different or changed `inputs`, or
+    /// never linked by forge at all.
+    pub fn link_needs_rerun(&self, target: &Path, config: &str, inputs: &[PathBuf]) -> bool {
+        let Some(entry) = self.links.get(target) else {
+            debug!("No link cache entry found for {:?}", target);
+            return true;
+        };
+
+        if entry.inputs_hash != Self::hash_str(config) {
+            debug!("Link configuration of {:?} changed", target);
+            return true;
+        }
+
+        if entry.inputs.len() != inputs.len() {
+            debug!("Link inputs of {:?} changed", target);
+            return true;
+        }
+
+        for input in inputs {
+            match entry.inputs.get(input) {
+                Some(info) if !self.file_changed_with_info(input, info) => {}
+                _ => {
+                    debug!("Link input {:?} of {:?} changed", input, target);
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    pub fn update_link(&mut self, target: &Path, config: &str, inputs: &[PathBuf]) -> ForgeResult<()> {
+        let mut input_infos = HashMap::new();
+        for input in inputs {
+            input_infos.insert(input.to_path_buf(), self.get_file_info(input)?);
+        }
+
+        self.links.insert(
+            target.to_path_buf(),
+            LinkEntry {
+                inputs_hash: Self::hash_str(config),
+                inputs: input_infos,
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+            },
+        );
+        self.links_dirty = true;
+
+        Ok(())
+    }
+
+    fn hash_str(value: &str) -> String {
+        format!("{:x}", Sha256::digest(value.as_bytes()))
+    }
+
     fn get_file_info(&self, path: &Path) -> ForgeResult<FileInfo> {
         let metadata = fs::metadata(path)
             .map_err(|e| ForgeError::Cache(format!("Failed to get metadata for {}: {}", path.display(), e)))?;
@@ -140,7 +434,7 @@ impl BuildCache {
             hash: if self.quick_check {
                 "quick_check".to_string()
             } else {
-                self.hash_file(path)?
+                Self::hash_file(path)?
             },
             mtime: metadata.modified()
                 .unwrap_or(UNIX_EPOCH)
@@ -151,19 +445,6 @@ impl BuildCache {
         })
     }
 
-    fn file_changed(&self, path: &Path, old_hash: &str) -> bool {
-        if let Ok(info) = self.get_file_info(path) {
-            if self.quick_check {
-                trace!("Quick check for {:?}", path);
-                false
-            } else {
-                info.hash != old_hash
-            }
-        } else {
-            true
-        }
-    }
-
     fn file_changed_with_info(&self, path: &Path, old_info: &FileInfo) -> bool {
         if let Ok(new_info) = self.get_file_info(path) {
             if self.quick_check {
@@ -182,7 +463,8 @@ impl BuildCache {
         }
     }
 
-    fn hash_file(&self, path: &Path) -> ForgeResult<String> {
+    /// The SHA-256 of `path`'s contents, in hex.
+    pub fn hash_file(path: &Path) -> ForgeResult<String> {
         let mut hasher = Sha256::new();
         let contents = fs::read(path)
             .map_err(|e| ForgeError::Cache(format!("Failed to read {}: {}", path.display(), e)))?;
@@ -191,25 +473,121 @@ impl BuildCache {
         Ok(format!("{:x}", hasher.finalize()))
     }
 
-    pub fn save(&self) -> ForgeResult<()> {
-        for (path, entry) in &self.entries {
-            let cache_path = self.cache_dir.join(format!(
-                "{}.cache",
-                path.file_name()
-                    .unwrap_or_default()
-                    .to_string_lossy()
-            ));
+    /// Entry files of older versions, one per object, were named after a
+    /// hash of the object path.
+    fn is_legacy_entry_file(path: &Path) -> bool {
+        path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|stem| stem.len() == 64 && stem.bytes().all(|b| b.is_ascii_hexdigit()))
+    }
 
-            let content = serde_json::to_string(entry)
-                .map_err(|e| ForgeError::Cache(format!("Failed to serialize cache: {}", e)))?;
+    /// Writes the tables that changed since they were loaded or last
+    /// saved. A table that became empty has its file removed.
+    pub fn save(&mut self) -> ForgeResult<()> {
+        if !(self.entries_dirty || self.generators_dirty || self.links_dirty) {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.cache_dir)
+            .map_err(|e| ForgeError::Cache(format!("Failed to create cache directory: {}", e)))?;
+
+        if self.entries_dirty {
+            let mut entries: Vec<&CacheEntry> = self.entries.values().collect();
+            entries.sort_by(|a, b| a.object.cmp(&b.object));
+            self.write_table(ENTRIES_FILE, &entries, entries.is_empty())?;
+
+            for path in self.legacy_files.drain(..) {
+                let _ = fs::remove_file(path);
+            }
+            self.entries_dirty = false;
+        }
+
+        if self.generators_dirty {
+            self.write_table(GENERATORS_FILE, &self.generators, self.generators.is_empty())?;
+            self.generators_dirty = false;
+        }
 
-            fs::write(&cache_path, content)
-                .map_err(|e| ForgeError::Cache(format!("Failed to write cache: {}", e)))?;
+        if self.links_dirty {
+            self.write_table(LINKS_FILE, &self.links, self.links.is_empty())?;
+            self.links_dirty = false;
         }
         Ok(())
     }
 
+    fn write_table(&self, file: &str, table: &impl Serialize, empty: bool) -> ForgeResult<()> {
+        let path = self.cache_dir.join(file);
+        if empty {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .map_err(|e| ForgeError::Cache(format!("Failed to remove {}: {}", file, e)))?;
+            }
+            return Ok(());
+        }
+
+        let content = serde_json::to_string(table)
+            .map_err(|e| ForgeError::Cache(format!("Failed to serialize cache: {}", e)))?;
+
+        // Written next to the table and renamed over it, so that an
+        // interrupted save leaves the old table rather than a truncated one
+        let temp = self.cache_dir.join(format!("{}.{}.tmp", file, std::process::id()));
+        let written = fs::File::create(&temp)
+            .and_then(|mut out| {
+                out.write_all(content.as_bytes())?;
+                out.sync_all()
+            })
+            .and_then(|_| fs::rename(&temp, &path));
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp);
+            return Err(ForgeError::Cache(format!("Failed to write cache: {}", e)));
+        }
+        Ok(())
+    }
+
+    /// Reads a table, treating a damaged file (e.g. truncated by a crash)
+    /// like a missing one: it only costs rebuilding what it described.
+    fn read_table<T: serde::de::DeserializeOwned>(&self, file: &str) -> ForgeResult<Option<T>> {
+        let path = self.cache_dir.join(file);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path)
+            .map_err(|e| ForgeError::Cache(format!("Failed to read cache file: {}", e)))?;
+
+        match serde_json::from_str(&content) {
+            Ok(table) => Ok(Some(table)),
+            Err(e) => {
+                debug!("Ignoring unreadable cache file {:?}: {}", path, e);
+                Ok(None)
+            }
+        }
+    }
+
     pub fn load(&mut self) -> ForgeResult<()> {
+        if !self.cache_dir.exists() {
+            return Ok(());
+        }
+
+        if let Some(entries) = self.read_table::<Vec<CacheEntry>>(ENTRIES_FILE)? {
+            for entry in entries {
+                self.entries.insert(entry.object.clone(), entry);
+            }
+        }
+        self.load_legacy_entries()?;
+
+        if let Some(generators) = self.read_table(GENERATORS_FILE)? {
+            self.generators = generators;
+        }
+        if let Some(links) = self.read_table(LINKS_FILE)? {
+            self.links = links;
+        }
+        Ok(())
+    }
+
+    /// Merges the per-object entry files written by older versions. They
+    /// are folded into the entries table on the next save.
+    fn load_legacy_entries(&mut self) -> ForgeResult<()> {
+        let mut entry_files = Vec::new();
         for entry in fs::read_dir(&self.cache_dir)
             .map_err(|e| ForgeError::Cache(format!("Failed to read cache directory: {}", e)))?
         {
@@ -217,21 +595,43 @@ impl BuildCache {
                 .map_err(|e| ForgeError::Cache(format!("Failed to read cache entry: {}", e)))?;
             let path = entry.path();
 
-            if path.extension().map_or(false, |ext| ext == "cache") {
-                let content = fs::read_to_string(&path)
-                    .map_err(|e| ForgeError::Cache(format!("Failed to read cache file: {}", e)))?;
+            if path.extension().is_some_and(|ext| ext == "cache") {
+                // Even older entries were named after the object's file
+                // name and can't be matched reliably; drop them so the
+                // affected sources are rebuilt.
+                if !Self::is_legacy_entry_file(&path) {
+                    debug!("Removing stale cache file {:?}", path);
+                    let _ = fs::remove_file(&path);
+                    continue;
+                }
+                entry_files.push(path);
+            }
+        }
 
-                let cache_entry: CacheEntry = serde_json::from_str(&content)
-                    .map_err(|e| ForgeError::Cache(format!("Failed to parse cache: {}", e)))?;
+        if entry_files.is_empty() {
+            return Ok(());
+        }
 
-                let source_name = path.file_stem()
-                    .unwrap_or_default()
-                    .to_string_lossy()
-                    .to_string();
+        let entries: Vec<CacheEntry> = entry_files.par_iter()
+            .filter_map(|path| {
+                let parsed = fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|content| serde_json::from_str::<CacheEntry>(&content).map_err(|e| e.to_string()));
+                match parsed {
+                    Ok(entry) => Some(entry),
+                    Err(e) => {
+                        debug!("Ignoring unreadable cache file {:?}: {}", path, e);
+                        None
+                    }
+                }
+            })
+            .collect();
 
-                self.entries.insert(PathBuf::from(source_name), cache_entry);
-            }
+        for entry in entries {
+            self.entries.entry(entry.object.clone()).or_insert(entry);
         }
+        self.legacy_files.extend(entry_files);
+        self.entries_dirty = true;
         Ok(())
     }
 
@@ -239,13 +639,75 @@ impl BuildCache {
         self.quick_check = enable;
     }
 
+    /// Forgets the objects and link outputs below one of `dirs`, and the
+    /// generators of `members`, keeping everything else cached.
+    pub fn clean_members(&mut self, dirs: &[PathBuf], members: &[&str]) -> ForgeResult<()> {
+        let below = |path: &Path| dirs.iter().any(|dir| path.starts_with(dir));
+
+        let removed: Vec<PathBuf> = self.entries.keys()
+            .filter(|object| below(object))
+            .cloned()
+            .collect();
+        for object in removed {
+            self.remove_entry(&object);
+        }
+
+        let links = self.links.len();
+        self.links.retain(|target, _| !below(target));
+        self.links_dirty |= self.links.len() != links;
+
+        let generators = self.generators.len();
+        self.generators.retain(|key, _| {
+            !members.iter().any(|member| key.starts_with(&format!("{}/", member)))
+        });
+        self.generators_dirty |= self.generators.len() != generators;
+
+        self.save()
+    }
+
+    /// Removes the entries below `dir` whose source file no longer exists,
+    /// along with their object files. Returns the removed objects.
+    pub fn prune(&mut self, dir: &Path) -> ForgeResult<Vec<PathBuf>> {
+        self.prune_where(dir, |source| !source.exists())
+    }
+
+    /// Removes the entries below `dir` whose source is not among `sources`,
+    /// e.g. as it is excluded now, along with their object files.
+    pub fn prune_unlisted(&mut self, dir: &Path, sources: &[PathBuf]) -> ForgeResult<Vec<PathBuf>> {
+        self.prune_where(dir, |source| !sources.iter().any(|s| s == source))
+    }
+
+    fn prune_where(&mut self, dir: &Path, orphaned: impl Fn(&Path) -> bool) -> ForgeResult<Vec<PathBuf>> {
+        let orphaned: Vec<PathBuf> = self.entries.values()
+            .filter(|entry| entry.object.starts_with(dir))
+            // Entries from before sources were recorded can't be judged
+            .filter(|entry| !entry.source_path.as_os_str().is_empty() && orphaned(&entry.source_path))
+            .map(|entry| entry.object.clone())
+            .collect();
+
+        for object in &orphaned {
+            debug!("Pruning {:?}", object);
+            self.remove_entry(object);
+            if object.exists() {
+                fs::remove_file(object)
+                    .map_err(|e| ForgeError::Cache(format!("Failed to remove {}: {}", object.display(), e)))?;
+            }
+        }
+
+        Ok(orphaned)
+    }
+
+    fn remove_entry(&mut self, object: &Path) {
+        if self.entries.remove(object).is_some() {
+            self.entries_dirty = true;
+        }
+    }
+
     pub fn clean(&self) -> ForgeResult<()> {
         if self.cache_dir.exists() {
             fs::remove_dir_all(&self.cache_dir)
                 .map_err(|e| ForgeError::Cache(format!("Failed to remove cache directory: {}", e)))?;
         }
-        fs::create_dir_all(&self.cache_dir)
-            .map_err(|e| ForgeError::Cache(format!("Failed to create cache directory: {}", e)))?;
         Ok(())
     }
 }
\ No newline at end of file
@@ -1,17 +1,72 @@
 use crate::{
-    config::{BuildProfile, CompilerConfig},
+    config::{BuildConfig, BuildProfile, CompilerConfig, CompilerFlavor, Language, OutputType, Sanitizer},
     error::{ForgeError, ForgeResult},
+    messages::{self, Messages},
+    target::OS,
     toolchains::Toolchain,
 };
+use indicatif::{MultiProgress, ProgressDrawTarget};
 use regex::Regex;
+use sha2::{Digest, Sha256};
 use std::{
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
     path::{Path, PathBuf},
-    process::Command,
+    io::{BufRead, BufReader, Read},
+    process::{Command, ExitStatus, Output, Stdio},
+    sync::Mutex,
 };
 
+/// Longest command line passed directly. Windows caps command lines at
+/// 32767 characters (8191 through cmd.exe); stay well below either.
+const COMMAND_LINE_LIMIT: usize = 8000;
+
+/// A header compiled ahead of the sources that include it first.
+pub struct PrecompiledHeader {
+    pub header: PathBuf,
+    pub output: PathBuf,
+    kind: PchKind,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PchKind {
+    /// `<header>.gch`, found by GCC next to the header it stands for
+    Gcc,
+    /// `<header>.pch`, passed to Clang with `-include-pch`
+    Clang,
+    /// `<header>.pch`, created by `cl /Yc` from a stub source
+    Msvc,
+}
+
+impl PrecompiledHeader {
+    /// The object MSVC emits along with the `.pch`, which has to be linked
+    /// into the target.
+    pub fn object(&self) -> Option<PathBuf> {
+        (self.kind == PchKind::Msvc).then(|| self.output.with_extension("obj"))
+    }
+
+    fn stub_source(&self) -> PathBuf {
+        self.output.with_extension("cpp")
+    }
+}
+
+/// What running a compiler for its version banner told about it.
+#[derive(Clone)]
+struct CompilerProbe {
+    /// The program with a hash of the whole banner
+    identity: String,
+    /// The banner's first line
+    version: Option<String>,
+}
+
 pub struct Compiler {
     include_regex: Regex,
     toolchain: Option<Toolchain>,
+    verbosity: u8,
+    messages: Messages,
+    progress_bars: MultiProgress,
+    probes: Mutex<HashMap<String, CompilerProbe>>,
+    strip: Option<bool>,
 }
 
 impl Compiler {
@@ -19,6 +74,110 @@ impl Compiler {
         Compiler {
             include_regex: Regex::new(r#"#include\s*[<"]([^>"]+)[>"]"#).unwrap(),
             toolchain,
+            verbosity: 0,
+            messages: Messages::default(),
+            progress_bars: MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+            probes: Mutex::new(HashMap::new()),
+            strip: None,
+        }
+    }
+
+    /// 1 echoes every command before running it, 2 also reports how
+    /// includes were resolved.
+    pub fn set_verbosity(&mut self, verbosity: u8) {
+        self.verbosity = verbosity;
+    }
+
+    pub fn set_messages(&mut self, messages: Messages) {
+        self.messages = messages;
+    }
+
+    /// Overrides the profiles' `strip` setting; `debug_info` still wins.
+    pub fn set_strip(&mut self, strip: Option<bool>) {
+        self.strip = strip;
+    }
+
+    /// Whether linked outputs get their symbols stripped. MSVC keeps debug
+    /// info in separate `.pdb` files and has nothing to strip.
+    pub fn strips(&self, profile: &BuildProfile, build: &BuildConfig) -> bool {
+        build.flavor() == CompilerFlavor::Gnu
+            && !profile.debug_info
+            && self.strip.or(profile.strip).unwrap_or(true)
+    }
+
+    /// Whether stripping is a separate step after linking, with the
+    /// toolchain's `strip`, rather than the driver's `-s`. ld64 ignores
+    /// `-s`, and cross toolchains' drivers aren't relied upon for it.
+    pub fn strips_after_link(&self, profile: &BuildProfile, build: &BuildConfig) -> bool {
+        self.strips(profile, build) && (self.toolchain.is_some() || self.targets_darwin())
+    }
+
+    /// The builder's progress bars, hidden while compiler output is shown.
+    pub fn set_progress_bars(&mut self, progress_bars: MultiProgress) {
+        self.progress_bars = progress_bars;
+    }
+
+    /// Position-independent code flags for `config`. These have no meaning
+    /// for Windows targets and are skipped there.
+    pub fn codegen_flags(&self, config: &CompilerConfig) -> Vec<&'static str> {
+        if self.targets_windows() {
+            vec![]
+        } else if config.pic {
+            vec!["-fPIC"]
+        } else if config.pie {
+            vec!["-fPIE"]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Flags the toolchain adds to every command, e.g. `--sysroot`.
+    pub fn toolchain_flags(&self) -> Vec<String> {
+        self.toolchain.as_ref().map(Toolchain::flags).unwrap_or_default()
+    }
+
+    /// The options made from settings other than plain flags, as they
+    /// reach the command line, by the setting they come from.
+    /// `[security]` holds them to its allowlist like flags.
+    pub fn configured_args(&self, config: &CompilerConfig, build: &BuildConfig) -> Vec<(&'static str, Vec<String>)> {
+        let flavor = build.flavor();
+        let (define, system_include, library_path) = match flavor {
+            CompilerFlavor::Gnu => ("-D", "-isystem", "-L"),
+            CompilerFlavor::Msvc => ("/D", "/external:I", "/LIBPATH:"),
+        };
+        let prefix_all = |prefix: &str, values: &[String]| -> Vec<String> {
+            values.iter().map(|value| format!("{}{}", prefix, value)).collect()
+        };
+
+        vec![
+            ("[compiler] definitions", config.definition_flags(define)),
+            ("[compiler] warnings", config.warning_flags(flavor)),
+            ("[compiler] system_include_paths", prefix_all(system_include, &config.system_include_paths)),
+            ("[compiler] library_paths", prefix_all(library_path, &config.library_paths)),
+            ("[compiler] libraries", match flavor {
+                CompilerFlavor::Gnu => prefix_all("-l", &config.libraries),
+                CompilerFlavor::Msvc => config.libraries.clone(),
+            }),
+            ("[compiler] rpath", prefix_all("-Wl,-rpath,", &config.rpath)),
+            ("[build] linker", build.linker.iter()
+                .flat_map(|linker| use_linker_args(linker, build.linker_driver()))
+                .collect()),
+            ("[build] compiler_wrapper", compiler_wrapper(build).into_iter().collect()),
+            ("the toolchain", self.toolchain_flags()),
+        ]
+    }
+
+    fn targets_windows(&self) -> bool {
+        match &self.toolchain {
+            Some(toolchain) => toolchain.target().is_windows(),
+            None => cfg!(windows),
+        }
+    }
+
+    fn targets_darwin(&self) -> bool {
+        match &self.toolchain {
+            Some(toolchain) => toolchain.target().os == OS::Darwin,
+            None => cfg!(target_os = "macos"),
         }
     }
 
@@ -34,6 +193,9 @@ impl Compiler {
             for dir in include_dirs {
                 let path = dir.join(header);
                 if path.exists() {
+                    if self.verbosity > 1 {
+                        self.messages.status(format_args!("  {}: {} -> {}", source_file.display(), header, path.display()));
+                    }
                     includes.push(path);
                     break;
                 }
@@ -43,6 +205,9 @@ impl Compiler {
         includes
     }
 
+    /// Compiles `source` into `object`. C++ sources get `pch` included
+    /// ahead of their own code.
+    #[allow(clippy::too_many_arguments)]
     pub fn compile(
         &self,
         source: &Path,
@@ -50,66 +215,265 @@ impl Compiler {
         config: &CompilerConfig,
         profile: &BuildProfile,
         include_dirs: &[PathBuf],
-        compiler: &str,
+        build: &BuildConfig,
+        pch: Option<&PrecompiledHeader>,
     ) -> ForgeResult<()> {
-        println!("Compiling {}", source.display());
-
         // Create directories if they don't exist
         if let Some(parent) = object.parent() {
             std::fs::create_dir_all(parent)
                 .map_err(|e| ForgeError::Compiler(format!("Failed to create directory: {}", e)))?;
         }
 
-        let mut cmd = if let Some(toolchain) = &self.toolchain {
-            toolchain.get_compiler_command(compiler)
-        } else {
-            Command::new(compiler)
+        let mut cmd = self.compile_command(source, object, config, profile, include_dirs, build, pch);
+        self.run_compiler(&mut cmd)
+    }
+
+    /// The command `compile` runs.
+    #[allow(clippy::too_many_arguments)]
+    pub fn compile_command(
+        &self,
+        source: &Path,
+        object: &Path,
+        config: &CompilerConfig,
+        profile: &BuildProfile,
+        include_dirs: &[PathBuf],
+        build: &BuildConfig,
+        pch: Option<&PrecompiledHeader>,
+    ) -> Command {
+        let pch = pch.filter(|_| Language::of(source) == Language::Cxx);
+        let mut cmd = self.compiler_command(build.compiler_for(Language::of(source)));
+        if let Some(wrapper) = compiler_wrapper(build) {
+            cmd = wrap_command(&cmd, &wrapper);
+        }
+        match build.flavor() {
+            CompilerFlavor::Gnu => {
+                if let Some(pch) = pch {
+                    Self::gnu_use_pch(&mut cmd, pch);
+                }
+                self.gnu_compile_args(&mut cmd, source, object, config, profile, include_dirs);
+                let compiler = build.compiler_for(Language::of(source));
+                cmd.args(profile.lto.compile_flags(CompilerFlavor::Gnu, compiler.contains("clang")));
+            }
+            CompilerFlavor::Msvc => {
+                Self::msvc_compile_args(&mut cmd, source, object, config, profile, include_dirs);
+                if let Some(pch) = pch {
+                    cmd.arg(prefixed("/Yu", &pch.header))
+                        .arg(prefixed("/FI", &pch.header))
+                        .arg(prefixed("/Fp", &pch.output));
+                }
+            }
+        }
+        cmd
+    }
+
+    /// Where the precompiled form of `header` goes inside `dir`, and how
+    /// the compiler of `build` is told to use it.
+    pub fn precompiled_header(&self, header: &Path, dir: &Path, build: &BuildConfig) -> PrecompiledHeader {
+        let kind = match build.flavor() {
+            CompilerFlavor::Msvc => PchKind::Msvc,
+            CompilerFlavor::Gnu if build.compiler_for(Language::Cxx).contains("clang") => PchKind::Clang,
+            CompilerFlavor::Gnu => PchKind::Gcc,
         };
 
-        cmd.arg("-c")
-            .arg(source)
+        let mut name = header.file_name().unwrap_or(header.as_os_str()).to_owned();
+        name.push(if kind == PchKind::Gcc { ".gch" } else { ".pch" });
+
+        PrecompiledHeader {
+            header: header.to_path_buf(),
+            output: dir.join(name),
+            kind,
+        }
+    }
+
+    /// Compiles `pch.header` with the same options as the C++ sources that
+    /// will use it; a precompiled header built with different options is
+    /// rejected or silently ignored by the compiler.
+    pub fn precompile_header(
+        &self,
+        pch: &PrecompiledHeader,
+        config: &CompilerConfig,
+        profile: &BuildProfile,
+        include_dirs: &[PathBuf],
+        build: &BuildConfig,
+    ) -> ForgeResult<()> {
+        self.messages.status(format_args!("Precompiling {}", pch.header.display()));
+
+        if let Some(parent) = pch.output.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| ForgeError::Compiler(format!("Failed to create directory: {}", e)))?;
+        }
+
+        let mut cmd = match self.precompile_command(pch, config, profile, include_dirs, build) {
+            Some(cmd) => cmd,
+            None => {
+                // cl only creates a .pch while compiling a source that
+                // includes the header
+                let stub = pch.stub_source();
+                std::fs::write(&stub, format!("#include \"{}\"\n", pch.header.display()))
+                    .map_err(|e| ForgeError::Compiler(format!("Failed to write {}: {}", stub.display(), e)))?;
+
+                let mut cmd = self.compiler_command(build.compiler_for(Language::Cxx));
+                let object = pch.object().unwrap_or_default();
+                Self::msvc_compile_args(&mut cmd, &stub, &object, config, profile, include_dirs);
+                cmd.arg(prefixed("/Yc", &pch.header))
+                    .arg(prefixed("/Fp", &pch.output));
+                cmd
+            }
+        };
+
+        self.run_compiler(&mut cmd)
+    }
+
+    /// The command precompiling `pch.header` with GCC or Clang. MSVC needs
+    /// a stub source written first, and has no such command.
+    pub fn precompile_command(
+        &self,
+        pch: &PrecompiledHeader,
+        config: &CompilerConfig,
+        profile: &BuildProfile,
+        include_dirs: &[PathBuf],
+        build: &BuildConfig,
+    ) -> Option<Command> {
+        if pch.kind == PchKind::Msvc {
+            return None;
+        }
+
+        let mut cmd = self.compiler_command(build.compiler_for(Language::Cxx));
+        cmd.args(["-x", "c++-header"]);
+        self.gnu_compile_args(&mut cmd, &pch.header, &pch.output, config, profile, include_dirs);
+        let compiler = build.compiler_for(Language::Cxx);
+        cmd.args(profile.lto.compile_flags(CompilerFlavor::Gnu, compiler.contains("clang")));
+        Some(cmd)
+    }
+
+    /// GCC picks up `<dir>/<header>.gch` while searching `<dir>` for the
+    /// forced include, so the directory has to come first.
+    fn gnu_use_pch(cmd: &mut Command, pch: &PrecompiledHeader) {
+        match pch.kind {
+            PchKind::Clang => {
+                cmd.arg("-include-pch").arg(&pch.output);
+            }
+            _ => {
+                if let Some(dir) = pch.output.parent() {
+                    cmd.arg(format!("-I{}", dir.display()));
+                }
+                cmd.arg("-include").arg(pch.header.file_name().unwrap_or_default());
+            }
+        }
+    }
+
+    fn run_compiler(&self, cmd: &mut Command) -> ForgeResult<()> {
+        let (status, output) = self.run_streamed(cmd)
+            .map_err(|e| ForgeError::Compiler(format!("Failed to execute compiler: {}", e)))?;
+
+        if !status.success() {
+            return Err(ForgeError::Compiler(self.failure_output(cmd, status, output)));
+        }
+
+        Ok(())
+    }
+
+    fn gnu_compile_args(
+        &self,
+        cmd: &mut Command,
+        source: &Path,
+        object: &Path,
+        config: &CompilerConfig,
+        profile: &BuildProfile,
+        include_dirs: &[PathBuf],
+    ) {
+        cmd.arg("-c");
+        if let Some(dialect) = Language::objc_dialect(source) {
+            cmd.args(["-x", dialect]);
+        }
+        cmd.arg(source)
             .arg("-o")
             .arg(object);
 
         for dir in include_dirs {
             cmd.arg(format!("-I{}", dir.display()));
         }
+        for dir in &config.system_include_paths {
+            cmd.args(["-isystem", dir]);
+        }
 
-        cmd.args(&config.flags);
+        let language = Language::of(source);
+        cmd.args(config.flags_for(language));
+        if let Some(standard) = config.standard(language) {
+            cmd.arg(format!("-std={}", standard));
+        }
+        cmd.args(self.codegen_flags(config));
         cmd.arg(format!("-O{}", profile.opt_level));
         if profile.debug_info {
             cmd.arg("-g");
         }
 
-        if profile.lto {
-            cmd.arg("-flto");
-        }
-
+        cmd.args(Sanitizer::flags(&profile.sanitizers, CompilerFlavor::Gnu));
         cmd.args(&profile.extra_flags);
 
-        for (key, value) in &config.definitions {
-            cmd.arg(format!("-D{}={}", key, value));
-        }
+        cmd.args(config.definition_flags("-D"));
 
-        for path in &config.library_paths {
+        for path in config.unique_library_paths() {
             cmd.arg(format!("-L{}", path));
         }
 
-        if config.warnings_as_errors {
-            cmd.arg("-Werror");
+        cmd.args(config.warning_flags(CompilerFlavor::Gnu));
+
+        if self.color_diagnostics() {
+            cmd.arg("-fdiagnostics-color=always");
         }
+    }
 
-        let output = cmd
-            .output()
-            .map_err(|e| ForgeError::Compiler(format!("Failed to execute compiler: {}", e)))?;
+    /// Compilers only color their diagnostics on a terminal, and they run
+    /// with their output piped; when it is passed on to a colored stderr,
+    /// ask for colors explicitly.
+    fn color_diagnostics(&self) -> bool {
+        self.messages.shows_tool_output() && messages::colors(&std::io::stderr())
+    }
 
-        if !output.status.success() {
-            return Err(ForgeError::Compiler(
-                String::from_utf8_lossy(&output.stderr).into_owned()
-            ));
+    fn msvc_compile_args(
+        cmd: &mut Command,
+        source: &Path,
+        object: &Path,
+        config: &CompilerConfig,
+        profile: &BuildProfile,
+        include_dirs: &[PathBuf],
+    ) {
+        cmd.args(["/nologo", "/c"])
+            .arg(source)
+            .arg(prefixed("/Fo", object));
+
+        for dir in include_dirs {
+            cmd.arg(prefixed("/I", dir));
+        }
+        if !config.system_include_paths.is_empty() {
+            cmd.arg("/external:W0");
+            for dir in &config.system_include_paths {
+                cmd.args(["/external:I", dir]);
+            }
         }
 
-        Ok(())
+        let language = Language::of(source);
+        cmd.args(config.flags_for(language));
+        if let Some(standard) = config.standard(language) {
+            cmd.arg(format!("/std:{}", standard));
+        }
+        cmd.arg(profile.msvc_opt_flag());
+
+        // /Z7 keeps debug info in the object instead of a shared .pdb, which
+        // parallel compiler processes would otherwise contend for
+        if profile.debug_info {
+            cmd.arg("/Z7");
+        }
+
+        cmd.args(profile.lto.compile_flags(CompilerFlavor::Msvc, false));
+
+        cmd.args(Sanitizer::flags(&profile.sanitizers, CompilerFlavor::Msvc));
+        cmd.args(&profile.extra_flags);
+
+        cmd.args(config.definition_flags("/D"));
+
+        cmd.args(config.warning_flags(CompilerFlavor::Msvc));
     }
 
     pub fn link(
@@ -118,26 +482,132 @@ impl Compiler {
         target: &Path,
         config: &CompilerConfig,
         profile: &BuildProfile,
-        compiler: &str,
+        build: &BuildConfig,
+        output_type: OutputType,
     ) -> ForgeResult<()> {
-        println!("Linking {}", target.display());
+        self.messages.status(format_args!("Linking {}", target.display()));
 
         if let Some(parent) = target.parent() {
             std::fs::create_dir_all(parent)
                 .map_err(|e| ForgeError::Compiler(format!("Failed to create directory: {}", e)))?;
         }
 
-        let mut cmd = if let Some(toolchain) = &self.toolchain {
-            toolchain.get_compiler_command(compiler)
-        } else {
-            Command::new(compiler)
-        };
+        let mut cmd = self.link_command(objects, target, config, profile, build, output_type);
+        let response_file = self.use_response_file(&mut cmd, target, build.flavor(), config.response_file)?;
+        let result = self.run_streamed(&mut cmd);
+        if let Some(path) = response_file {
+            let _ = std::fs::remove_file(path);
+        }
+        let (status, output) = result
+            .map_err(|e| ForgeError::Compiler(format!("Failed to execute linker: {}", e)))?;
+
+        if !status.success() {
+            let output = self.failure_output(&cmd, status, output);
+            if let Some(linker) = build.linker.as_deref().filter(|linker| !linker_exists(linker)) {
+                return Err(ForgeError::Compiler(format!(
+                    "Linker {} not found (configured as [build] linker): {}",
+                    linker,
+                    output
+                )));
+            }
+            return Err(ForgeError::Compiler(output));
+        }
+
+        Ok(())
+    }
 
+    /// The command `link` runs, before any response file.
+    pub fn link_command(
+        &self,
+        objects: &[PathBuf],
+        target: &Path,
+        config: &CompilerConfig,
+        profile: &BuildProfile,
+        build: &BuildConfig,
+        output_type: OutputType,
+    ) -> Command {
+        let mut cmd = self.compiler_command(build.linker_driver());
+        match build.flavor() {
+            CompilerFlavor::Gnu => {
+                self.gnu_link_args(&mut cmd, objects, target, config, profile, output_type);
+                let clang = build.linker_driver().contains("clang");
+                cmd.args(profile.lto.link_flags(CompilerFlavor::Gnu, clang, profile.lto_jobs));
+                if let Some(linker) = &build.linker {
+                    cmd.args(use_linker_args(linker, build.linker_driver()));
+                }
+                if self.strips(profile, build) && !self.strips_after_link(profile, build) {
+                    cmd.arg("-s");
+                }
+                if build.emit_map {
+                    // ld64 spells it differently from GNU ld, gold, lld and mold
+                    let option = if self.targets_darwin() { "-Wl,-map," } else { "-Wl,-Map=" };
+                    cmd.arg(prefixed(option, &map_file(target)));
+                }
+            }
+            CompilerFlavor::Msvc => {
+                Self::msvc_link_args(&mut cmd, objects, target, config, profile, output_type);
+                if build.emit_map {
+                    cmd.arg(prefixed("/MAP:", &map_file(target)));
+                }
+            }
+        }
+        cmd
+    }
+
+    /// Moves the arguments of `cmd` into `<target>.rsp` and passes `@file`
+    /// instead, when forced or when the command line would get too long
+    /// for the OS. Returns the file to remove once the command has run.
+    fn use_response_file(
+        &self,
+        cmd: &mut Command,
+        target: &Path,
+        flavor: CompilerFlavor,
+        force: bool,
+    ) -> ForgeResult<Option<PathBuf>> {
+        let length: usize = cmd.get_args().map(|arg| arg.len() + 1).sum();
+        if !force && length <= COMMAND_LINE_LIMIT {
+            return Ok(None);
+        }
+
+        let contents: Vec<String> = cmd.get_args()
+            .map(|arg| quote_response_arg(&arg.to_string_lossy(), flavor))
+            .collect();
+
+        let mut path = target.as_os_str().to_owned();
+        path.push(".rsp");
+        let path = PathBuf::from(path);
+        std::fs::write(&path, contents.join("\n"))
+            .map_err(|e| ForgeError::Compiler(format!("Failed to write response file: {}", e)))?;
+
+        let mut response_cmd = Command::new(cmd.get_program());
+        response_cmd.arg(prefixed("@", &path));
+        copy_envs(cmd, &mut response_cmd);
+        *cmd = response_cmd;
+        Ok(Some(path))
+    }
+
+    fn gnu_link_args(
+        &self,
+        cmd: &mut Command,
+        objects: &[PathBuf],
+        target: &Path,
+        config: &CompilerConfig,
+        profile: &BuildProfile,
+        output_type: OutputType,
+    ) {
         cmd.args(objects)
             .arg("-o")
             .arg(target);
 
-        for path in &config.library_paths {
+        if self.color_diagnostics() {
+            cmd.arg("-fdiagnostics-color=always");
+        }
+
+        if output_type == OutputType::Shared {
+            cmd.arg("-shared");
+        }
+
+        for path in config.unique_library_paths() {
             cmd.arg(format!("-L{}", path));
         }
 
@@ -145,27 +615,369 @@ impl Compiler {
             cmd.arg(format!("-l{}", lib));
         }
 
-        if profile.lto {
-            cmd.arg("-flto");
+        if !self.targets_windows() {
+            for path in &config.rpath {
+                cmd.arg(format!("-Wl,-rpath,{}", path));
+            }
         }
 
+        if output_type == OutputType::Executable && config.pie && !self.targets_windows() {
+            cmd.arg("-pie");
+        }
+
+        cmd.args(Sanitizer::flags(&profile.sanitizers, CompilerFlavor::Gnu));
         cmd.args(&profile.extra_flags);
-        let output = cmd
-            .output()
-            .map_err(|e| ForgeError::Compiler(format!("Failed to execute linker: {}", e)))?;
+    }
+
+    /// Links through `cl`, which forwards everything after `/link` to
+    /// `link.exe`. Libraries are named by their `.lib` file; shared
+    /// libraries produce a `.dll` plus an import `.lib` next to it.
+    fn msvc_link_args(
+        cmd: &mut Command,
+        objects: &[PathBuf],
+        target: &Path,
+        config: &CompilerConfig,
+        profile: &BuildProfile,
+        output_type: OutputType,
+    ) {
+        cmd.arg("/nologo")
+            .args(objects)
+            .arg(prefixed("/Fe", target));
+
+        if output_type == OutputType::Shared {
+            cmd.arg("/LD");
+        }
+
+        cmd.args(Sanitizer::flags(&profile.sanitizers, CompilerFlavor::Msvc));
+        cmd.args(&profile.extra_flags);
+        cmd.arg("/link");
+
+        for path in config.unique_library_paths() {
+            cmd.arg(format!("/LIBPATH:{}", path));
+        }
+
+        for lib in &config.libraries {
+            if lib.ends_with(".lib") {
+                cmd.arg(lib);
+            } else {
+                cmd.arg(format!("{}.lib", lib));
+            }
+        }
+
+        cmd.args(profile.lto.link_flags(CompilerFlavor::Msvc, false, profile.lto_jobs));
+
+        if profile.debug_info {
+            cmd.arg("/DEBUG");
+        }
+    }
+
+    pub fn archive(&self, objects: &[PathBuf], target: &Path, flavor: CompilerFlavor) -> ForgeResult<()> {
+        self.messages.status(format_args!("Archiving {}", target.display()));
+
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| ForgeError::Compiler(format!("Failed to create directory: {}", e)))?;
+        }
+
+        // ar only adds or replaces members, so start from scratch to drop
+        // objects whose sources were removed
+        if target.exists() {
+            std::fs::remove_file(target)
+                .map_err(|e| ForgeError::Compiler(format!("Failed to remove old archive: {}", e)))?;
+        }
+
+        let tool = if flavor == CompilerFlavor::Msvc { "lib" } else { "ar" };
+        self.run_tool_command(tool, &mut self.archive_command(objects, target, flavor))
+    }
+
+    /// The command `archive` runs. `ar` adds to an existing archive, which
+    /// has to be removed first.
+    pub fn archive_command(&self, objects: &[PathBuf], target: &Path, flavor: CompilerFlavor) -> Command {
+        if flavor == CompilerFlavor::Msvc {
+            let mut cmd = self.tool_command("lib");
+            cmd.arg("/nologo").arg(prefixed("/OUT:", target)).args(objects);
+            return cmd;
+        }
+
+        let mut cmd = self.tool_command("ar");
+        cmd.arg("rcs").arg(target).args(objects);
+        cmd
+    }
+
+    /// Moves debug info out of `binary` into `<binary>.debug` (linked back
+    /// with a GNU debuglink), or into `<binary>.dSYM` when targeting Darwin.
+    pub fn split_debug_info(&self, binary: &Path) -> ForgeResult<()> {
+        if self.targets_darwin() {
+            let dsym = binary.with_extension("dSYM");
+            self.run_tool("dsymutil", &[binary.as_os_str(), "-o".as_ref(), dsym.as_os_str()])?;
+            self.run_tool("strip", &["-S".as_ref(), binary.as_os_str()])?;
+        } else {
+            let mut debug_file = binary.as_os_str().to_owned();
+            debug_file.push(".debug");
+            let mut debuglink = std::ffi::OsString::from("--add-gnu-debuglink=");
+            debuglink.push(&debug_file);
+
+            self.run_tool("objcopy", &["--only-keep-debug".as_ref(), binary.as_os_str(), &debug_file])?;
+            self.run_tool("objcopy", &["--strip-debug".as_ref(), binary.as_os_str()])?;
+            self.run_tool("objcopy", &[&debuglink, binary.as_os_str()])?;
+        }
+
+        Ok(())
+    }
+
+    /// Strips the symbols from `binary` with the toolchain's `strip`, or
+    /// `llvm-strip` next to Clang. On Darwin only local symbols are
+    /// removed, which is what dynamic libraries allow.
+    pub fn strip(&self, binary: &Path, build: &BuildConfig) -> ForgeResult<()> {
+        let tool = if build.linker_driver().contains("clang") { "llvm-strip" } else { "strip" };
+        if self.targets_darwin() {
+            self.run_tool(tool, &["-x".as_ref(), binary.as_os_str()])
+        } else {
+            self.run_tool(tool, &[binary.as_os_str()])
+        }
+    }
+
+    /// Returns the entries of `required` that `binary` does not define as
+    /// global symbols, according to the target's `nm`. Stripped binaries
+    /// only have their dynamic symbols left to look at.
+    pub fn missing_symbols(&self, binary: &Path, required: &[String], stripped: bool) -> ForgeResult<Vec<String>> {
+        let mut cmd = self.tool_command("nm");
+        cmd.args(["-g", "--defined-only"]);
+        if stripped {
+            cmd.arg("-D");
+        }
+        cmd.arg(binary);
+        let output = self.execute(&mut cmd)
+            .map_err(|e| ForgeError::Compiler(format!("Failed to execute nm: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(ForgeError::Compiler(format!(
+                "nm failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let defined: HashSet<&str> = stdout.lines()
+            .filter_map(|line| line.split_whitespace().last())
+            .collect();
+
+        Ok(required.iter()
+            .filter(|symbol| {
+                !defined.contains(symbol.as_str())
+                    && !defined.contains(format!("_{}", symbol).as_str())
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// The section sizes `size` reports for `binary`, in its column order:
+    /// text, data and bss for ELF and PE files, segments for Mach-O.
+    pub fn section_sizes(&self, binary: &Path) -> ForgeResult<Vec<(String, u64)>> {
+        let mut cmd = self.tool_command("size");
+        cmd.arg(binary);
+        let output = self.execute(&mut cmd)
+            .map_err(|e| ForgeError::Compiler(format!("Failed to execute size: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(ForgeError::Compiler(format!(
+                "size failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut lines = stdout.lines();
+        let (Some(header), Some(values)) = (lines.next(), lines.next()) else {
+            return Err(ForgeError::Compiler(format!("Unexpected output from size: {}", stdout.trim())));
+        };
+
+        // The columns after the sections are totals and the file name
+        Ok(header.split_whitespace()
+            .zip(values.split_whitespace())
+            .take_while(|(name, _)| !matches!(*name, "dec" | "hex" | "filename"))
+            .filter_map(|(name, value)| Some((name.to_string(), value.parse().ok()?)))
+            .collect())
+    }
+
+    fn execute(&self, cmd: &mut Command) -> std::io::Result<Output> {
+        self.echo(cmd);
+        cmd.output()
+    }
+
+    /// Runs a compiler or linker, passing every line it prints on to
+    /// stderr as soon as it is complete (see `Messages::shows_tool_output`),
+    /// and returns everything it printed.
+    fn run_streamed(&self, cmd: &mut Command) -> std::io::Result<(ExitStatus, String)> {
+        self.echo(cmd);
+        let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+        let stdout = child.stdout.take().expect("piped stdout");
+        let stderr = child.stderr.take().expect("piped stderr");
+
+        // MSVC reports diagnostics on stdout, GCC and Clang on stderr
+        let (stdout, stderr) = std::thread::scope(|scope| {
+            let stdout = scope.spawn(|| self.forward_output(stdout));
+            let stderr = self.forward_output(stderr);
+            (stdout.join().expect("output reader panicked"), stderr)
+        });
+
+        Ok((child.wait()?, stdout + &stderr))
+    }
+
+    fn forward_output(&self, output: impl Read) -> String {
+        let mut reader = BufReader::new(output);
+        let mut captured = String::new();
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line).is_ok_and(|read| read > 0) {
+            let text = String::from_utf8_lossy(&line);
+            self.progress_bars.suspend(|| self.messages.tool_output(text.trim_end_matches(['\r', '\n'])));
+            captured.push_str(&text);
+            line.clear();
+        }
+        captured
+    }
+
+    /// The error text for a failed command: what it printed, unless that
+    /// was already shown as it ran.
+    fn failure_output(&self, cmd: &Command, status: ExitStatus, output: String) -> String {
+        if self.messages.shows_tool_output() {
+            format!(
+                "{} exited with code {}",
+                cmd.get_program().to_string_lossy(),
+                status.code().unwrap_or(-1)
+            )
+        } else {
+            output
+        }
+    }
+
+    fn echo(&self, cmd: &Command) {
+        if self.verbosity > 0 {
+            let argv: Vec<String> = std::iter::once(cmd.get_program())
+                .chain(cmd.get_args())
+                .map(|arg| {
+                    let arg = arg.to_string_lossy();
+                    if arg.contains(char::is_whitespace) {
+                        format!("\"{}\"", arg)
+                    } else {
+                        arg.into_owned()
+                    }
+                })
+                .collect();
+            self.messages.status(format_args!("  {}", argv.join(" ")));
+        }
+    }
+
+    /// The program `compiler` runs as, with a hash of its version banner,
+    /// so that switching or upgrading compilers invalidates cached
+    /// objects.
+    pub fn identity(&self, compiler: &str, flavor: CompilerFlavor) -> String {
+        self.probe(compiler, flavor).identity
+    }
+
+    /// Where `compiler` is found: in the toolchain when cross-compiling,
+    /// else in PATH.
+    pub fn compiler_path(&self, compiler: &str) -> Option<PathBuf> {
+        match &self.toolchain {
+            Some(toolchain) => toolchain.resolve_compiler(compiler).ok(),
+            None => find_program(compiler),
+        }
+    }
+
+    /// The first line of `compiler`'s version banner, e.g. `g++ (GCC) 13.2.0`.
+    pub fn version(&self, compiler: &str, flavor: CompilerFlavor) -> Option<String> {
+        self.probe(compiler, flavor).version
+    }
+
+    /// Runs `compiler` for its version banner, once per compiler.
+    fn probe(&self, compiler: &str, flavor: CompilerFlavor) -> CompilerProbe {
+        if let Some(probe) = self.probes.lock().unwrap().get(compiler) {
+            return probe.clone();
+        }
+
+        let mut cmd = self.compiler_command(compiler);
+        let program = cmd.get_program().to_string_lossy().into_owned();
+
+        // cl has no --version; it prints its banner to stderr when run
+        // without input
+        if flavor == CompilerFlavor::Gnu {
+            cmd.arg("--version");
+        }
+        let probe = match cmd.output() {
+            Ok(output) => {
+                let banner = [output.stdout, output.stderr].concat();
+                let hash = format!("{:x}", Sha256::digest(&banner));
+                CompilerProbe {
+                    identity: format!("{} {}", program, &hash[..16]),
+                    version: String::from_utf8_lossy(&banner).lines()
+                        .map(str::trim)
+                        .find(|line| !line.is_empty())
+                        .map(String::from),
+                }
+            }
+            Err(_) => CompilerProbe { identity: program, version: None },
+        };
+
+        self.probes.lock().unwrap()
+            .entry(compiler.to_string())
+            .or_insert(probe)
+            .clone()
+    }
+
+    /// Fails early when `compiler` can't be run for `member`: when it is
+    /// not in PATH, or when cross-compiling and the toolchain has no binary
+    /// for the target.
+    pub fn verify_compiler(&self, compiler: &str, member: &str) -> ForgeResult<()> {
+        match &self.toolchain {
+            Some(toolchain) => toolchain.verify(compiler),
+            None if find_program(compiler).is_some() => Ok(()),
+            None => Err(ForgeError::Config(format!(
+                "Compiler '{}' of {} not found",
+                compiler,
+                member
+            ))),
+        }
+    }
+
+    fn compiler_command(&self, compiler: &str) -> Command {
+        match &self.toolchain {
+            Some(toolchain) => toolchain.get_compiler_command(compiler),
+            None => Command::new(compiler),
+        }
+    }
+
+    fn tool_command(&self, tool: &str) -> Command {
+        match &self.toolchain {
+            Some(toolchain) => toolchain.get_tool_command(tool),
+            None => Command::new(tool),
+        }
+    }
+
+    fn run_tool(&self, tool: &str, args: &[&OsStr]) -> ForgeResult<()> {
+        let mut cmd = self.tool_command(tool);
+        cmd.args(args);
+        self.run_tool_command(tool, &mut cmd)
+    }
+
+    fn run_tool_command(&self, tool: &str, cmd: &mut Command) -> ForgeResult<()> {
+        let output = self.execute(cmd)
+            .map_err(|e| ForgeError::Compiler(format!("Failed to execute {}: {}", tool, e)))?;
 
         if !output.status.success() {
-            return Err(ForgeError::Compiler(
-                String::from_utf8_lossy(&output.stderr).into_owned()
-            ));
+            return Err(ForgeError::Compiler(format!(
+                "{} failed: {}",
+                tool,
+                String::from_utf8_lossy(&output.stderr)
+            )));
         }
 
         Ok(())
     }
 
-    pub fn get_object_path(&self, source: &Path, build_dir: &Path) -> PathBuf {
-        let stem = source.file_stem().unwrap().to_str().unwrap();
-        build_dir.join(format!("{}.o", stem))
+    /// Object path for a source given relative to its member, mirroring
+    /// its directories so that equally named sources don't collide.
+    pub fn get_object_path(&self, relative_source: &Path, build_dir: &Path) -> PathBuf {
+        build_dir.join(relative_source).with_extension("o")
     }
 }
 
@@ -173,4 +985,255 @@ impl Default for Compiler {
     fn default() -> Self {
         Self::new(None)
     }
-}
\ No newline at end of file
+}
+
+/// The linker map written for `target` with `[build] emit_map`.
+fn map_file(target: &Path) -> PathBuf {
+    let mut path = target.as_os_str().to_owned();
+    path.push(".map");
+    PathBuf::from(path)
+}
+
+/// The program to run compiles through (e.g. `ccache`): the
+/// `FORGE_COMPILER_WRAPPER` environment variable if set, where an empty
+/// value disables wrapping, else `[build] compiler_wrapper`.
+fn compiler_wrapper(build: &BuildConfig) -> Option<String> {
+    match std::env::var("FORGE_COMPILER_WRAPPER") {
+        Ok(wrapper) => Some(wrapper).filter(|w| !w.is_empty()),
+        Err(_) => build.compiler_wrapper.clone(),
+    }
+}
+
+/// Driver options selecting `linker`. A name goes to `-fuse-ld=`; Clang
+/// takes a path through `--ld-path=`, while GCC has to be pointed at the
+/// directory holding `ld.<name>`.
+fn use_linker_args(linker: &str, driver: &str) -> Vec<String> {
+    let path = Path::new(linker);
+    if path.components().count() == 1 {
+        return vec![format!("-fuse-ld={}", linker)];
+    }
+
+    if driver.contains("clang") {
+        return vec![format!("--ld-path={}", linker)];
+    }
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = name.strip_prefix("ld.").unwrap_or(&name);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    vec![format!("-B{}", dir.display()), format!("-fuse-ld={}", name)]
+}
+
+/// Whether `linker` is an existing file, or, given by name, whether
+/// `ld.<name>` or `<name>` is in PATH. Only used to explain a failed link,
+/// so compiler-private search directories aren't considered.
+fn linker_exists(linker: &str) -> bool {
+    if Path::new(linker).components().count() > 1 {
+        return Path::new(linker).is_file();
+    }
+
+    find_program(&format!("ld.{}", linker)).or_else(|| find_program(linker)).is_some()
+}
+
+/// `program` if it is an existing file, or, given by name, where it is
+/// found in PATH.
+fn find_program(program: &str) -> Option<PathBuf> {
+    if Path::new(program).components().count() > 1 {
+        return Some(PathBuf::from(program)).filter(|path| path.is_file());
+    }
+
+    let names = [program.to_string(), format!("{}{}", program, std::env::consts::EXE_SUFFIX)];
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// `wrapper <program> <args>` for a command `<program> <args>`.
+fn wrap_command(cmd: &Command, wrapper: &str) -> Command {
+    let mut wrapped = Command::new(wrapper);
+    wrapped.arg(cmd.get_program()).args(cmd.get_args());
+    copy_envs(cmd, &mut wrapped);
+    wrapped
+}
+
+/// Gives `to` the environment changes made on `from`.
+fn copy_envs(from: &Command, to: &mut Command) {
+    for (key, value) in from.get_envs() {
+        match value {
+            Some(value) => to.env(key, value),
+            None => to.env_remove(key),
+        };
+    }
+}
+
+/// Quotes an argument for a response file. GCC and Clang treat backslashes
+/// as escapes there, while MSVC only escapes quotes.
+fn quote_response_arg(arg: &str, flavor: CompilerFlavor) -> String {
+    let escaped = match flavor {
+        CompilerFlavor::Gnu => arg.replace('\\', "\\\\").replace('"', "\\\""),
+        CompilerFlavor::Msvc => arg.replace('"', "\\\""),
+    };
+    format!("\"{}\"", escaped)
+}
+
+/// `prefix` immediately followed by `path`, as MSVC options like `/Fo`
+/// expect.
+fn prefixed(prefix: &str, path: &Path) -> OsString {
+    let mut arg = OsString::from(prefix);
+    arg.push(path);
+    arg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn compile_args(config: &Config, flavor: CompilerFlavor) -> Vec<OsString> {
+        let mut build = config.build.clone();
+        build.compiler_flavor = Some(flavor);
+        let profile = &config.profiles["release"];
+        Compiler::new(None)
+            .compile_command(
+                Path::new("src/main.cpp"),
+                Path::new("build/obj/main.o"),
+                &config.compiler,
+                profile,
+                &[PathBuf::from("include"), PathBuf::from("deps/include")],
+                &build,
+                None,
+            )
+            .get_args()
+            .map(OsStr::to_os_string)
+            .collect()
+    }
+
+    fn config_with(definitions: impl IntoIterator<Item = (String, String)>) -> Config {
+        let mut config = Config::default_for_member("app");
+        config.compiler.definitions.extend(definitions);
+        config.compiler.system_include_paths = vec!["/opt/sdk/include".to_string()];
+        config.compiler.warnings = vec!["shadow".to_string(), "conversion".to_string()];
+        config
+    }
+
+    #[test]
+    fn compile_command_is_deterministic() {
+        let definitions: HashMap<String, String> = (0..16)
+            .map(|i| (format!("DEF_{}", i), i.to_string()))
+            .chain([("NDEBUG".to_string(), String::new())])
+            .collect();
+        let mut reversed: Vec<_> = definitions.clone().into_iter().collect();
+        reversed.reverse();
+
+        let config = config_with(definitions);
+        let other = config_with(reversed);
+        for flavor in [CompilerFlavor::Gnu, CompilerFlavor::Msvc] {
+            let args = compile_args(&config, flavor);
+            assert_eq!(args, compile_args(&config, flavor));
+            assert_eq!(args, compile_args(&other, flavor));
+        }
+
+        let args = compile_args(&config, CompilerFlavor::Gnu);
+        let defines: Vec<_> = args.iter()
+            .filter_map(|arg| arg.to_str()?.strip_prefix("-D")?.split('=').next())
+            .collect();
+        let mut sorted = defines.clone();
+        sorted.sort();
+        assert_eq!(defines.len(), 17);
+        assert_eq!(defines, sorted);
+    }
+
+    #[test]
+    fn configured_args_are_held_to_the_allowlist() {
+        let mut config = Config::default_for_member("app");
+        config.compiler.definitions.insert("EVIL".to_string(), "1".to_string());
+        config.compiler.libraries = vec!["evil".to_string()];
+        config.compiler.library_paths = vec!["/evil/lib".to_string()];
+        config.compiler.system_include_paths = vec!["/evil/include".to_string()];
+        config.compiler.rpath = vec!["/evil/lib".to_string()];
+        config.build.linker = Some("lld".to_string());
+        config.build.compiler_wrapper = Some("evil-wrapper".to_string());
+
+        let args = Compiler::new(None).configured_args(&config.compiler, &config.build);
+        let flat: Vec<&str> = args.iter().flat_map(|(_, args)| args).map(String::as_str).collect();
+        for expected in ["-DEVIL=1", "-levil", "-L/evil/lib", "-isystem/evil/include", "-Wl,-rpath,/evil/lib", "-fuse-ld=lld"] {
+            assert!(flat.contains(&expected), "{} missing from {:?}", expected, flat);
+        }
+
+        let security = crate::config::SecurityConfig {
+            allowed_flag_prefixes: vec!["-O".to_string(), "-std".to_string()],
+        };
+        for (setting, args) in &args {
+            if !args.is_empty() {
+                assert!(security.check_flags(setting, args).is_err(), "{} got past the allowlist", setting);
+            }
+        }
+    }
+
+    #[test]
+    fn link_args_dedup_paths_but_keep_library_order() {
+        let mut config = Config::default_for_member("app");
+        config.compiler.library_paths = ["/opt/a", "/opt/b", "/opt/a"].map(String::from).to_vec();
+        config.compiler.libraries = ["net", "z", "net"].map(String::from).to_vec();
+
+        let mut cmd = Command::new("g++");
+        Compiler::new(None).gnu_link_args(
+            &mut cmd,
+            &[PathBuf::from("main.o")],
+            Path::new("app"),
+            &config.compiler,
+            &config.profiles["release"],
+            OutputType::Executable,
+        );
+        let args: Vec<_> = cmd.get_args().filter_map(OsStr::to_str).collect();
+        let paths: Vec<_> = args.iter().copied().filter(|arg| arg.starts_with("-L")).collect();
+        let libraries: Vec<_> = args.iter().copied().filter(|arg| arg.starts_with("-l")).collect();
+        assert_eq!(paths, ["-L/opt/a", "-L/opt/b"]);
+        assert_eq!(libraries, ["-lnet", "-lz", "-lnet"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn forge_cxx_picks_the_spawned_compiler() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let stub = dir.path().join("stub-c++");
+        let spawned = dir.path().join("spawned");
+        std::fs::write(&stub, format!("#!/bin/sh\necho \"$@\" > '{}'\n", spawned.display())).unwrap();
+        std::fs::set_permissions(&stub, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(
+            dir.path().join("forge.toml"),
+            "[build]\ncompiler = \"g++\"\ntarget = \"app\"\n\n[paths]\nsrc = \".\"\ninclude = []\n\n[compiler]\nflags = []\n",
+        ).unwrap();
+
+        let stub_env = |name: &str| (name == "FORGE_CXX").then(|| stub.display().to_string());
+        let config = Config::load_with_env(&dir.path().join("forge.toml"), &stub_env).unwrap();
+        assert_eq!(config.build.compiler_for(Language::Cxx), stub.to_str().unwrap());
+
+        let source = dir.path().join("main.cpp");
+        std::fs::write(&source, "int main() {}\n").unwrap();
+        Compiler::new(None)
+            .compile(
+                &source,
+                &dir.path().join("obj/main.o"),
+                &config.compiler,
+                &config.profiles["debug"],
+                &[],
+                &config.build,
+                None,
+            )
+            .unwrap();
+
+        let args = std::fs::read_to_string(&spawned).expect("the FORGE_CXX compiler did not run");
+        assert!(args.contains(source.to_str().unwrap()), "{}", args);
+
+        // Validation sees the overriding compiler, not the configured one
+        std::fs::write(
+            dir.path().join("forge.toml"),
+            "[build]\ncompiler = \"g++\"\ntarget = \"app\"\nlinker = \"lld\"\n\n[paths]\nsrc = \".\"\ninclude = []\n\n[compiler]\nflags = []\n",
+        ).unwrap();
+        let msvc_env = |name: &str| (name == "FORGE_CXX").then(|| "cl.exe".to_string());
+        assert!(Config::load_with_env(&dir.path().join("forge.toml"), &msvc_env).is_err());
+    }
+}
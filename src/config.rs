@@ -1,10 +1,13 @@
 use crate::error::{ForgeError, ForgeResult};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
+    #[serde(default)]
+    pub package: Option<PackageConfig>,
     pub build: BuildConfig,
     pub paths: PathConfig,
     pub compiler: CompilerConfig,
@@ -16,26 +19,361 @@ pub struct Config {
     pub profiles: HashMap<String, BuildProfile>,
     #[serde(default)]
     pub testing: Option<TestConfig>,
+    #[serde(default)]
+    pub generate: Vec<GenerateRule>,
+    #[serde(default)]
+    pub security: Option<SecurityConfig>,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    #[serde(default)]
+    pub install: InstallConfig,
+    #[serde(default)]
+    pub run: RunConfig,
+    #[serde(default)]
+    pub doc: DocConfig,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PackageConfig {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
 }
 
+/// Linkers that `[build] linker` may name, as understood by `-fuse-ld=`.
+const KNOWN_LINKERS: &[&str] = &["bfd", "gold", "lld", "mold"];
+
+/// Optimization levels GCC and Clang accept as `-O<level>`.
+const OPT_LEVELS: &[&str] = &["0", "1", "2", "3", "s", "z", "g", "fast"];
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct BuildConfig {
     pub compiler: String,
+    #[serde(default)]
+    pub cc: Option<String>,
+    #[serde(default)]
+    pub cxx: Option<String>,
     pub target: String,
     #[serde(default)]
     pub jobs: Option<usize>,
+    #[serde(default)]
+    pub default_target: Option<String>,
+    /// Environment for every cross toolchain's compiler and tools, which
+    /// a member's `[cross] env` adds to
+    #[serde(default)]
+    pub toolchain_env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub output_type: OutputType,
+    #[serde(default)]
+    pub compiler_flavor: Option<CompilerFlavor>,
+    #[serde(default)]
+    pub compiler_wrapper: Option<String>,
+    #[serde(default)]
+    pub linker: Option<String>,
+    #[serde(default)]
+    pub cache_mode: CacheMode,
+    #[serde(default)]
+    pub clean_mode: CleanMode,
+    #[serde(default)]
+    pub require_symbols: Vec<String>,
+    /// Write a linker map next to linked outputs and report their size
+    #[serde(default)]
+    pub emit_map: bool,
     #[serde(default = "default_profile")]
     pub default_profile: String,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputType {
+    #[default]
+    Executable,
+    Static,
+    Shared,
+    /// Header-only: nothing is compiled or linked, dependents only get
+    /// its include directories and libraries
+    Interface,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    C,
+    Cxx,
+}
+
+impl Language {
+    /// The language of a source file extension forge knows how to compile.
+    /// Objective-C (`.m`) goes to the C compiler and Objective-C++ (`.mm`)
+    /// to the C++ one.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "c" | "m" => Some(Language::C),
+            "cpp" | "cxx" | "cc" | "c++" | "mm" => Some(Language::Cxx),
+            _ => None,
+        }
+    }
+
+    /// `.c` and `.m` files are C, every other source is C++.
+    pub fn of(source: &Path) -> Self {
+        source.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Language::from_extension)
+            .unwrap_or(Language::Cxx)
+    }
+
+    /// The `-x` language for Objective-C sources, which take the C or C++
+    /// flags but have to be compiled as Objective-C.
+    pub fn objc_dialect(source: &Path) -> Option<&'static str> {
+        match source.extension()?.to_str()? {
+            "m" => Some("objective-c"),
+            "mm" => Some("objective-c++"),
+            _ => None,
+        }
+    }
+}
+
+/// How the build cache decides whether a file changed: by modification
+/// time and size, or by content hash.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMode {
+    #[default]
+    Quick,
+    Hash,
+}
+
+/// What `forge clean` removes by default: build outputs and the cache,
+/// or only one of them.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CleanMode {
+    #[default]
+    All,
+    Artifacts,
+    Cache,
+}
+
+/// Command line syntax spoken by the compiler: GCC/Clang style, or MSVC
+/// `cl.exe` style.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompilerFlavor {
+    Gnu,
+    Msvc,
+}
+
+impl CompilerFlavor {
+    pub fn detect(compiler: &str) -> Self {
+        let name = Path::new(compiler)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match name.as_str() {
+            "cl" | "clang-cl" => CompilerFlavor::Msvc,
+            _ => CompilerFlavor::Gnu,
+        }
+    }
+}
+
+/// Runtime checks compiled in with `-fsanitize=`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Sanitizer {
+    Address,
+    Undefined,
+    Thread,
+    Memory,
+    Leak,
+}
+
+impl Sanitizer {
+    pub fn name(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "address",
+            Sanitizer::Undefined => "undefined",
+            Sanitizer::Thread => "thread",
+            Sanitizer::Memory => "memory",
+            Sanitizer::Leak => "leak",
+        }
+    }
+
+    /// The compile and link flags enabling `sanitizers`. MSVC only has
+    /// AddressSanitizer, which `cl` links in by itself.
+    pub fn flags(sanitizers: &[Sanitizer], flavor: CompilerFlavor) -> Vec<String> {
+        if sanitizers.is_empty() {
+            return vec![];
+        }
+
+        match flavor {
+            CompilerFlavor::Gnu => {
+                let names: Vec<&str> = sanitizers.iter().map(|s| s.name()).collect();
+                vec![format!("-fsanitize={}", names.join(",")), "-fno-omit-frame-pointer".to_string()]
+            }
+            CompilerFlavor::Msvc => vec!["/fsanitize=address".to_string()],
+        }
+    }
+
+    /// Rejects sanitizers that can't be combined, or that `flavor` lacks.
+    /// `context` names where the list came from in the error.
+    pub fn check(context: &str, sanitizers: &[Sanitizer], flavor: CompilerFlavor) -> ForgeResult<()> {
+        use Sanitizer::*;
+        let conflicts = [(Address, Thread), (Address, Memory), (Thread, Memory), (Thread, Leak), (Memory, Leak)];
+        for (a, b) in conflicts {
+            if sanitizers.contains(&a) && sanitizers.contains(&b) {
+                return Err(ForgeError::Config(format!(
+                    "{}: the {} and {} sanitizers can't be used together",
+                    context,
+                    a.name(),
+                    b.name()
+                )));
+            }
+        }
+
+        if flavor == CompilerFlavor::Msvc {
+            if let Some(sanitizer) = sanitizers.iter().find(|s| **s != Address) {
+                return Err(ForgeError::Config(format!(
+                    "{}: the {} sanitizer is not supported with MSVC compilers",
+                    context,
+                    sanitizer.name()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Sanitizer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "address" => Ok(Sanitizer::Address),
+            "undefined" => Ok(Sanitizer::Undefined),
+            "thread" => Ok(Sanitizer::Thread),
+            "memory" => Ok(Sanitizer::Memory),
+            "leak" => Ok(Sanitizer::Leak),
+            _ => Err(format!(
+                "Unknown sanitizer: {} (expected address, undefined, thread, memory or leak)",
+                s
+            )),
+        }
+    }
+}
+
+/// Link-time optimization of a profile: `lto = "full"` (or `true`),
+/// `"thin"` or `false`.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(try_from = "LtoSetting")]
+pub enum Lto {
+    #[default]
+    Off,
+    Full,
+    /// Clang's ThinLTO; GCC has none and does full LTO instead
+    Thin,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LtoSetting {
+    Enabled(bool),
+    Mode(String),
+}
+
+impl TryFrom<LtoSetting> for Lto {
+    type Error = String;
+
+    fn try_from(setting: LtoSetting) -> Result<Self, Self::Error> {
+        match setting {
+            LtoSetting::Enabled(false) => Ok(Lto::Off),
+            LtoSetting::Enabled(true) => Ok(Lto::Full),
+            LtoSetting::Mode(mode) => match mode.as_str() {
+                "full" => Ok(Lto::Full),
+                "thin" => Ok(Lto::Thin),
+                _ => Err(format!("Unknown LTO mode: {} (expected full, thin or false)", mode)),
+            },
+        }
+    }
+}
+
+impl Serialize for Lto {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Lto::Off => serializer.serialize_bool(false),
+            Lto::Full => serializer.serialize_str("full"),
+            Lto::Thin => serializer.serialize_str("thin"),
+        }
+    }
+}
+
+impl Lto {
+    /// Flags for compiling sources into objects the link can optimize
+    /// across. `clang` tells Clang apart from GCC, which share a flavor.
+    pub fn compile_flags(self, flavor: CompilerFlavor, clang: bool) -> Vec<String> {
+        let flag = match (self, flavor) {
+            (Lto::Off, _) => return vec![],
+            (_, CompilerFlavor::Msvc) => "/GL",
+            (Lto::Thin, CompilerFlavor::Gnu) if clang => "-flto=thin",
+            (_, CompilerFlavor::Gnu) => "-flto",
+        };
+        vec![flag.to_string()]
+    }
+
+    /// Flags for linking with LTO, running `jobs` parallel code generation
+    /// jobs when given. Clang only parallelizes ThinLTO.
+    pub fn link_flags(self, flavor: CompilerFlavor, clang: bool, jobs: Option<usize>) -> Vec<String> {
+        let mut flags = Vec::new();
+        match (self, flavor) {
+            (Lto::Off, _) => {}
+            (_, CompilerFlavor::Msvc) => {
+                flags.push(if self == Lto::Thin { "/LTCG:INCREMENTAL" } else { "/LTCG" }.to_string());
+                flags.extend(jobs.map(|jobs| format!("/CGTHREADS:{}", jobs)));
+            }
+            (Lto::Thin, CompilerFlavor::Gnu) if clang => {
+                flags.push("-flto=thin".to_string());
+                flags.extend(jobs.map(|jobs| format!("-flto-jobs={}", jobs)));
+            }
+            (_, CompilerFlavor::Gnu) if clang => flags.push("-flto".to_string()),
+            (_, CompilerFlavor::Gnu) => flags.push(match jobs {
+                Some(jobs) => format!("-flto={}", jobs),
+                None => "-flto".to_string(),
+            }),
+        }
+        flags
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PathConfig {
     #[serde(default)]
     pub src: String,
+    #[serde(default)]
+    pub src_roots: Vec<String>,
     #[serde(default = "default_include_paths")]
     pub include: Vec<String>,
-    #[serde(default = "default_build_path")]
-    pub build: String,
+    /// Include directories exported to dependent members; `include` when
+    /// not set
+    #[serde(default)]
+    pub public_include: Option<Vec<String>>,
+    /// Build directory: the workspace's when set in its root, and a
+    /// member's own, relative to it, when set by the member
+    #[serde(default)]
+    pub build: Option<String>,
+    /// Extensions of the files compiled as sources, without the dot
+    #[serde(default = "default_source_extensions")]
+    pub source_extensions: Vec<String>,
+    /// Files and globs relative to the member compiled instead of what is
+    /// found in the source directories
+    #[serde(default)]
+    pub sources: Option<Vec<String>>,
+    /// Globs over paths relative to the source directory (or file names)
+    /// of sources left out of the build
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Whether sources are looked for in subdirectories too
+    #[serde(default = "default_recursive")]
+    pub recursive: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -43,13 +381,38 @@ pub struct CompilerConfig {
     #[serde(default)]
     pub flags: Vec<String>,
     #[serde(default)]
-    pub definitions: HashMap<String, String>,
+    pub definitions: BTreeMap<String, String>,
     #[serde(default)]
     pub warnings_as_errors: bool,
+    /// Warning groups enabled as `-W<name>`, e.g. `all` or `extra`
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Warnings kept non-fatal under `warnings_as_errors`, as
+    /// `-Wno-error=<name>`
+    #[serde(default)]
+    pub warnings_allowed: Vec<String>,
     #[serde(default)]
     pub library_paths: Vec<String>,
+    /// Include directories whose headers are treated as system headers,
+    /// so that their warnings are suppressed
+    #[serde(default)]
+    pub system_include_paths: Vec<String>,
     #[serde(default)]
     pub libraries: Vec<String>,
+    #[serde(default)]
+    pub rpath: Vec<String>,
+    #[serde(default)]
+    pub response_file: bool,
+    #[serde(default)]
+    pub c_standard: Option<String>,
+    #[serde(default)]
+    pub cxx_standard: Option<String>,
+    #[serde(default)]
+    pub precompiled_header: Option<String>,
+    #[serde(default)]
+    pub pic: bool,
+    #[serde(default)]
+    pub pie: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
@@ -59,6 +422,11 @@ pub struct WorkspaceConfig {
     #[serde(default)]
     pub exclude: Vec<String>,
     #[serde(default)]
+    pub auto_discover: bool,
+    /// Members that commands act on when none are named
+    #[serde(default)]
+    pub default_members: Vec<String>,
+    #[serde(default)]
     pub dependencies: HashMap<String, Vec<String>>,
 }
 
@@ -69,31 +437,247 @@ pub struct CrossConfig {
     pub sysroot: Option<PathBuf>,
     #[serde(default)]
     pub extra_flags: Vec<String>,
+    /// Environment the toolchain's compiler and tools run with
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct BuildProfile {
     pub opt_level: String,
     pub debug_info: bool,
-    pub lto: bool,
+    pub lto: Lto,
+    /// Parallel code generation jobs for LTO links
+    #[serde(default)]
+    pub lto_jobs: Option<usize>,
+    #[serde(default)]
+    pub split_debug: bool,
+    /// Strip symbols from linked outputs; on by default for profiles
+    /// without `debug_info`, and never done with it
+    #[serde(default)]
+    pub strip: Option<bool>,
+    #[serde(default)]
+    pub sanitizers: Vec<Sanitizer>,
     #[serde(default)]
     pub extra_flags: Vec<String>,
+    #[serde(default)]
+    pub compiler: ProfileCompilerConfig,
+}
+
+/// `[profiles.<name>.compiler]`: additions to a member's `[compiler]`
+/// section that only apply when building with that profile.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ProfileCompilerConfig {
+    #[serde(default)]
+    pub flags: Vec<String>,
+    #[serde(default)]
+    pub definitions: BTreeMap<String, String>,
+    #[serde(default)]
+    pub library_paths: Vec<String>,
+    #[serde(default)]
+    pub libraries: Vec<String>,
+}
+
+/// Shell commands run in the member directory before compiling and after
+/// linking a member.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct HooksConfig {
+    #[serde(default)]
+    pub pre_build: Vec<String>,
+    #[serde(default)]
+    pub post_build: Vec<String>,
+}
+
+/// Files `forge install` copies besides the member's output and include
+/// directories. Headers go to `<prefix>/include`, other files to
+/// `<prefix>/share/<target>`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct InstallConfig {
+    #[serde(default)]
+    pub headers: Vec<String>,
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+/// Defaults for `forge run`. `cwd` is relative to the member directory;
+/// arguments given on the command line replace `args`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RunConfig {
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Program, with arguments, that the executable and test binaries are
+    /// run under, e.g. an emulator for cross builds
+    #[serde(default)]
+    pub runner: Option<String>,
+}
+
+/// Settings for `forge doc`. Without a Doxyfile, one is written that
+/// documents `input` (the source and include directories by default);
+/// `options` are added as Doxyfile tags in either case.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct DocConfig {
+    #[serde(default)]
+    pub doxyfile: Option<String>,
+    #[serde(default)]
+    pub input: Option<Vec<String>>,
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+}
+
+/// Test frameworks whose libraries, patterns and `main` forge knows.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TestFramework {
+    Gtest,
+    Catch2,
+    Doctest,
+}
+
+impl TestFramework {
+    fn patterns(self) -> &'static [&'static str] {
+        match self {
+            TestFramework::Gtest => &["*_test.cpp", "*_unittest.cpp", "test_*.cpp"],
+            TestFramework::Catch2 | TestFramework::Doctest => &["*_test.cpp", "*_tests.cpp", "test_*.cpp"],
+        }
+    }
+
+    fn libraries(self) -> &'static [&'static str] {
+        match self {
+            TestFramework::Gtest => &["gtest_main", "gtest", "pthread"],
+            TestFramework::Catch2 => &["Catch2Main", "Catch2"],
+            TestFramework::Doctest => &[],
+        }
+    }
+
+    /// The source of a `main` for frameworks that don't ship one as a
+    /// library.
+    pub fn main_source(self) -> Option<&'static str> {
+        match self {
+            TestFramework::Doctest => Some("#define DOCTEST_CONFIG_IMPLEMENT_WITH_MAIN\n#include <doctest/doctest.h>\n"),
+            TestFramework::Gtest | TestFramework::Catch2 => None,
+        }
+    }
+}
+
+/// Whether tests are linked into one executable, or each test source into
+/// its own one (next to the shared `main`, if any).
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum TestMode {
+    #[default]
+    Single,
+    PerFile,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TestConfig {
-    #[serde(default = "default_test_patterns")]
-    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub framework: Option<TestFramework>,
+    #[serde(default)]
+    pub mode: TestMode,
+    /// Overrides the framework's (or the default) patterns when set
+    #[serde(default)]
+    pub patterns: Option<Vec<String>>,
     pub test_dir: Option<String>,
     #[serde(default)]
     pub exclude: Vec<String>,
     #[serde(default)]
     pub flags: Vec<String>,
+    /// Overrides the framework's libraries when set
     #[serde(default)]
-    pub libs: Vec<String>,
+    pub libs: Option<Vec<String>>,
     pub main: Option<String>,
 }
 
+impl TestConfig {
+    pub fn patterns(&self) -> Vec<String> {
+        match (&self.patterns, self.framework) {
+            (Some(patterns), _) => patterns.clone(),
+            (None, Some(framework)) => framework.patterns().iter().map(|p| p.to_string()).collect(),
+            (None, None) => default_test_patterns(),
+        }
+    }
+
+    pub fn libs(&self) -> Vec<String> {
+        match (&self.libs, self.framework) {
+            (Some(libs), _) => libs.clone(),
+            (None, Some(framework)) => framework.libraries().iter().map(|l| l.to_string()).collect(),
+            (None, None) => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct GenerateRule {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    #[serde(default)]
+    pub outputs: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SecurityConfig {
+    #[serde(default)]
+    pub allowed_flag_prefixes: Vec<String>,
+}
+
+impl SecurityConfig {
+    pub fn check_flags(&self, origin: &str, flags: &[String]) -> ForgeResult<()> {
+        let rejected: Vec<&str> = flags.iter()
+            .filter(|flag| !self.allowed_flag_prefixes.iter().any(|p| flag.starts_with(p.as_str())))
+            .map(String::as_str)
+            .collect();
+
+        if rejected.is_empty() {
+            return Ok(());
+        }
+
+        Err(ForgeError::Config(format!(
+            "Flags not permitted by [security] allowed_flag_prefixes in {}: {}",
+            origin,
+            rejected.join(" ")
+        )))
+    }
+}
+
+/// Replaces every `${VAR}` in `value` with the variable's value.
+fn expand_env_vars(value: &str, env: &dyn Fn(&str) -> Option<String>) -> ForgeResult<String> {
+    let mut result = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find('}').ok_or_else(|| ForgeError::Config(format!(
+            "Unterminated ${{ in {}",
+            value
+        )))?;
+
+        let name = &after[..end];
+        let var = env(name).ok_or_else(|| ForgeError::Config(format!(
+            "Environment variable {} is not set (used in {})",
+            name,
+            value
+        )))?;
+        result.push_str(&var);
+        rest = &after[end + 1..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// A variable of the process environment, which configs are expanded from.
+pub fn process_env(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
 fn default_profile() -> String {
     "debug".to_string()
 }
@@ -102,8 +686,12 @@ fn default_include_paths() -> Vec<String> {
     vec!["include".to_string()]
 }
 
-fn default_build_path() -> String {
-    "build".to_string()
+fn default_source_extensions() -> Vec<String> {
+    ["cpp", "c", "cc"].map(String::from).to_vec()
+}
+
+fn default_recursive() -> bool {
+    true
 }
 
 fn default_test_patterns() -> Vec<String> {
@@ -114,19 +702,219 @@ impl Default for PathConfig {
     fn default() -> Self {
         Self {
             src: String::new(),
+            src_roots: vec![],
             include: default_include_paths(),
-            build: default_build_path(),
+            public_include: None,
+            build: None,
+            source_extensions: default_source_extensions(),
+            sources: None,
+            exclude: vec![],
+            recursive: default_recursive(),
+        }
+    }
+}
+
+impl PathConfig {
+    /// The configured build directory, `build` by default.
+    pub fn build_path(&self) -> &str {
+        self.build.as_deref().unwrap_or("build")
+    }
+}
+
+impl BuildConfig {
+    /// `cc` or `cxx` for the language, falling back to `compiler`.
+    pub fn compiler_for(&self, language: Language) -> &str {
+        let specific = match language {
+            Language::C => self.cc.as_deref(),
+            Language::Cxx => self.cxx.as_deref(),
+        };
+        specific.unwrap_or(&self.compiler)
+    }
+
+    /// Lets `FORGE_CC` and `FORGE_CXX` pick the compilers over the
+    /// configuration, as CI jobs do. `FORGE_CXX` replaces `compiler` as
+    /// well, so that the flavor and the fallback for C follow it.
+    pub fn apply_compiler_env(&mut self, env: &dyn Fn(&str) -> Option<String>) {
+        let var = |name| env(name).filter(|value| !value.is_empty());
+        if let Some(cxx) = var("FORGE_CXX") {
+            self.compiler = cxx.clone();
+            self.cxx = Some(cxx);
+        }
+        if let Some(cc) = var("FORGE_CC") {
+            self.cc = Some(cc);
+        }
+    }
+
+    /// The driver used to link, which has to be the C++ one so that the C++
+    /// runtime gets linked.
+    pub fn linker_driver(&self) -> &str {
+        self.compiler_for(Language::Cxx)
+    }
+
+    /// The configured `compiler_flavor`, or the one implied by the
+    /// compiler's name.
+    pub fn flavor(&self) -> CompilerFlavor {
+        self.compiler_flavor.unwrap_or_else(|| CompilerFlavor::detect(&self.compiler))
+    }
+
+    /// `linker` has to name a linker GCC and Clang know (`-fuse-ld=`), or
+    /// be a path to one. MSVC always links with `link.exe`.
+    fn check_linker(&self) -> ForgeResult<()> {
+        let Some(linker) = &self.linker else {
+            return Ok(());
+        };
+
+        if self.flavor() == CompilerFlavor::Msvc {
+            return Err(ForgeError::Config(format!(
+                "linker = \"{}\" is not supported with MSVC compilers",
+                linker
+            )));
+        }
+
+        let is_path = Path::new(linker).components().count() > 1;
+        if !is_path && !KNOWN_LINKERS.contains(&linker.as_str()) {
+            return Err(ForgeError::Config(format!(
+                "Unknown linker '{}' (expected one of {} or a path)",
+                linker,
+                KNOWN_LINKERS.join(", ")
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl BuildProfile {
+    /// Accepts `opt_level` with or without a leading `-O`/`O`, and stores
+    /// just the level. MSVC maps the levels onto `/Od`, `/O1` and `/O2`.
+    fn normalize_opt_level(&mut self, profile: &str) -> ForgeResult<()> {
+        let level = self.opt_level.trim();
+        let level = level.strip_prefix("-O")
+            .or_else(|| level.strip_prefix('O'))
+            .unwrap_or(level);
+
+        if !OPT_LEVELS.contains(&level) {
+            return Err(ForgeError::Config(format!(
+                "Invalid opt_level '{}' in profile {} (expected one of {})",
+                self.opt_level,
+                profile,
+                OPT_LEVELS.join(", ")
+            )));
+        }
+
+        self.opt_level = level.to_string();
+        Ok(())
+    }
+
+    /// The MSVC flag for the normalized `opt_level`.
+    pub fn msvc_opt_flag(&self) -> &'static str {
+        match self.opt_level.as_str() {
+            "0" | "g" => "/Od",
+            "1" | "s" | "z" => "/O1",
+            _ => "/O2",
+        }
+    }
+}
+
+impl CompilerConfig {
+    /// The configured standard for `language`, e.g. `c11` or `c++17`. A bare
+    /// number is taken as a version of the language.
+    pub fn standard(&self, language: Language) -> Option<String> {
+        let (standard, prefix) = match language {
+            Language::C => (self.c_standard.as_ref()?, "c"),
+            Language::Cxx => (self.cxx_standard.as_ref()?, "c++"),
+        };
+
+        if standard.chars().all(|c| c.is_ascii_digit()) {
+            Some(format!("{}{}", prefix, standard))
+        } else {
+            Some(standard.clone())
+        }
+    }
+
+    /// `flags` without `-std=` flags meant for the other language, so
+    /// that a shared `-std=c++17` doesn't reach C sources and vice versa.
+    pub fn flags_for(&self, language: Language) -> impl Iterator<Item = &String> {
+        self.flags.iter().filter(move |flag| {
+            let Some(standard) = flag.strip_prefix("-std=").or_else(|| flag.strip_prefix("/std:")) else {
+                return true;
+            };
+            let is_cxx = standard.contains("++");
+            is_cxx == (language == Language::Cxx)
+        })
+    }
+
+    /// `definitions` as `<prefix>KEY=VALUE` options in key order. An empty
+    /// value defines the bare name, e.g. `-DNDEBUG`.
+    pub fn definition_flags(&self, prefix: &str) -> Vec<String> {
+        self.definitions.iter()
+            .map(|(key, value)| match value.is_empty() {
+                true => format!("{}{}", prefix, key),
+                false => format!("{}{}={}", prefix, key, value),
+            })
+            .collect()
+    }
+
+    /// `warnings` and `warnings_as_errors` as options for `flavor`. MSVC
+    /// has no named warning groups, so any of them selects `/W4`, and
+    /// `warnings_allowed` only applies to GCC and Clang.
+    pub fn warning_flags(&self, flavor: CompilerFlavor) -> Vec<String> {
+        let name = |warning: &String| warning.strip_prefix("-W").unwrap_or(warning).to_string();
+        let mut flags = Vec::new();
+        match flavor {
+            CompilerFlavor::Gnu => {
+                flags.extend(self.warnings.iter().map(|w| format!("-W{}", name(w))));
+                if self.warnings_as_errors {
+                    flags.push("-Werror".to_string());
+                    flags.extend(self.warnings_allowed.iter().map(|w| format!("-Wno-error={}", name(w))));
+                }
+            }
+            CompilerFlavor::Msvc => {
+                if !self.warnings.is_empty() {
+                    flags.push("/W4".to_string());
+                }
+                if self.warnings_as_errors {
+                    flags.push("/WX".to_string());
+                }
+            }
         }
+        flags
+    }
+
+    /// `library_paths` with duplicates removed, keeping the first occurrence.
+    /// Only membership matters for search paths; `libraries` keep their
+    /// order (and duplicates) since it is significant for static linking.
+    pub fn unique_library_paths(&self) -> Vec<&str> {
+        let mut seen = HashSet::new();
+        self.library_paths.iter()
+            .map(String::as_str)
+            .filter(|path| seen.insert(*path))
+            .collect()
     }
 }
 
 impl Config {
     pub fn load(path: &Path) -> ForgeResult<Self> {
+        Self::load_with_env(path, &process_env)
+    }
+
+    /// Loads `path`, expanding it and picking compilers from `env`
+    /// rather than the process environment.
+    pub(crate) fn load_with_env(path: &Path, env: &dyn Fn(&str) -> Option<String>) -> ForgeResult<Self> {
         let content = std::fs::read_to_string(path)
             .map_err(|e| ForgeError::Config(format!("Failed to read config: {}", e)))?;
 
         let mut config: Config = toml::from_str(&content)
             .map_err(|e| ForgeError::Config(format!("Failed to parse config: {}", e)))?;
+        config.expand_env(env)?;
+        // Checked against the compiler that will actually run
+        config.build.apply_compiler_env(env);
+        config.build.check_linker()?;
+        config.check_source_extensions()?;
+        for (name, profile) in &mut config.profiles {
+            profile.normalize_opt_level(name)?;
+            Sanitizer::check(&format!("profile {}", name), &profile.sanitizers, config.build.flavor())?;
+        }
 
         if !config.profiles.contains_key(&config.build.default_profile) {
             config.profiles.insert(
@@ -134,8 +922,13 @@ impl Config {
                 BuildProfile {
                     opt_level: "0".to_string(),
                     debug_info: true,
-                    lto: false,
+                    lto: Lto::Off,
+                    lto_jobs: None,
+                    split_debug: false,
+                    strip: None,
+                    sanitizers: vec![],
                     extra_flags: vec![],
+                    compiler: ProfileCompilerConfig::default(),
                 },
             );
         }
@@ -143,55 +936,282 @@ impl Config {
         Ok(config)
     }
 
+    /// Every source extension has to map to a language, so that files are
+    /// never handed to the wrong compiler. MSVC can't compile Objective-C.
+    fn check_source_extensions(&self) -> ForgeResult<()> {
+        for extension in &self.paths.source_extensions {
+            let extension = extension.strip_prefix('.').unwrap_or(extension);
+            if Language::from_extension(extension).is_none() {
+                return Err(ForgeError::Config(format!(
+                    "Unknown source extension '{}' (expected one of c, cpp, cxx, cc, c++, m, mm)",
+                    extension
+                )));
+            }
+
+            if matches!(extension, "m" | "mm") && self.build.flavor() == CompilerFlavor::Msvc {
+                return Err(ForgeError::Config(format!(
+                    "Objective-C sources (.{}) are not supported with MSVC compilers",
+                    extension
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Expands `${VAR}` in compiler, path and flag settings from `env`
+    /// (the process environment outside of tests). Commands
+    /// (`[[generate]]`, `[hooks]`) are left alone since they are expanded
+    /// by the shell that runs them.
+    fn expand_env(&mut self, env: &dyn Fn(&str) -> Option<String>) -> ForgeResult<()> {
+        fn expand_all<'a>(
+            values: impl IntoIterator<Item = &'a mut String>,
+            env: &dyn Fn(&str) -> Option<String>,
+        ) -> ForgeResult<()> {
+            for value in values {
+                *value = expand_env_vars(value, env)?;
+            }
+            Ok(())
+        }
+
+        let build = &mut self.build;
+        expand_all([&mut build.compiler, &mut build.target, &mut build.default_profile], env)?;
+        expand_all([
+            &mut build.cc,
+            &mut build.cxx,
+            &mut build.default_target,
+            &mut build.compiler_wrapper,
+            &mut build.linker,
+        ].into_iter().flatten(), env)?;
+        expand_all(build.toolchain_env.values_mut(), env)?;
+        expand_all(&mut build.require_symbols, env)?;
+
+        self.paths.src = expand_env_vars(&self.paths.src, env)?;
+        expand_all(&mut self.paths.src_roots, env)?;
+        if let Some(sources) = &mut self.paths.sources {
+            expand_all(sources, env)?;
+        }
+        expand_all(&mut self.paths.include, env)?;
+        if let Some(public_include) = &mut self.paths.public_include {
+            expand_all(public_include, env)?;
+        }
+        if let Some(build) = &mut self.paths.build {
+            *build = expand_env_vars(build, env)?;
+        }
+
+        let compiler = &mut self.compiler;
+        expand_all(&mut compiler.flags, env)?;
+        expand_all(compiler.definitions.values_mut(), env)?;
+        expand_all(&mut compiler.warnings, env)?;
+        expand_all(&mut compiler.warnings_allowed, env)?;
+        expand_all(&mut compiler.library_paths, env)?;
+        expand_all(&mut compiler.system_include_paths, env)?;
+        expand_all(&mut compiler.libraries, env)?;
+        expand_all(&mut compiler.rpath, env)?;
+        expand_all([
+            &mut compiler.c_standard,
+            &mut compiler.cxx_standard,
+            &mut compiler.precompiled_header,
+        ].into_iter().flatten(), env)?;
+
+        if let Some(cross) = &mut self.cross {
+            cross.target = expand_env_vars(&cross.target, env)?;
+            if let Some(toolchain) = &mut cross.toolchain {
+                *toolchain = expand_env_vars(toolchain, env)?;
+            }
+            if let Some(sysroot) = &mut cross.sysroot {
+                *sysroot = PathBuf::from(expand_env_vars(&sysroot.to_string_lossy(), env)?);
+            }
+            expand_all(&mut cross.extra_flags, env)?;
+            for value in cross.env.values_mut() {
+                *value = expand_env_vars(value, env)?;
+            }
+        }
+
+        if let Some(runner) = &mut self.run.runner {
+            *runner = expand_env_vars(runner, env)?;
+        }
+
+        expand_all(&mut self.install.headers, env)?;
+        expand_all(&mut self.install.files, env)?;
+        if let Some(doxyfile) = &mut self.doc.doxyfile {
+            *doxyfile = expand_env_vars(doxyfile, env)?;
+        }
+        if let Some(input) = &mut self.doc.input {
+            expand_all(input, env)?;
+        }
+
+        for profile in self.profiles.values_mut() {
+            profile.opt_level = expand_env_vars(&profile.opt_level, env)?;
+            expand_all(&mut profile.extra_flags, env)?;
+            expand_all(&mut profile.compiler.flags, env)?;
+            expand_all(profile.compiler.definitions.values_mut(), env)?;
+            expand_all(&mut profile.compiler.library_paths, env)?;
+            expand_all(&mut profile.compiler.libraries, env)?;
+        }
+
+        Ok(())
+    }
+
     pub fn default_for_member(name: &str) -> Self {
         let mut config = Config {
+            package: None,
             build: BuildConfig {
                 compiler: "g++".to_string(),
+                cc: None,
+                cxx: None,
                 target: name.to_string(),
                 jobs: None,
+                default_target: None,
+                toolchain_env: BTreeMap::new(),
+                output_type: OutputType::Executable,
+                compiler_flavor: None,
+                compiler_wrapper: None,
+                linker: None,
+                cache_mode: CacheMode::Quick,
+                clean_mode: CleanMode::All,
+                require_symbols: vec![],
+                emit_map: false,
                 default_profile: "debug".to_string(),
             },
             paths: PathConfig::default(),
             compiler: CompilerConfig {
                 flags: vec!["-Wall".to_string(), "-std=c++17".to_string()],
-                definitions: HashMap::new(),
+                definitions: BTreeMap::new(),
                 warnings_as_errors: false,
+                warnings: vec![],
+                warnings_allowed: vec![],
                 library_paths: vec![],
+                system_include_paths: vec![],
                 libraries: vec![],
+                rpath: vec![],
+                response_file: false,
+                c_standard: None,
+                cxx_standard: None,
+                precompiled_header: None,
+                pic: false,
+                pie: false,
             },
             workspace: WorkspaceConfig::default(),
             cross: None,
             profiles: HashMap::new(),
             testing: Some(TestConfig {
-                patterns: default_test_patterns(),
+                framework: None,
+                mode: TestMode::Single,
+                patterns: None,
                 test_dir: None,
                 exclude: vec![],
                 flags: vec![],
-                libs: vec![],
+                libs: None,
                 main: None,
             }),
+            generate: vec![],
+            security: None,
+            hooks: HooksConfig::default(),
+            install: InstallConfig::default(),
+            run: RunConfig::default(),
+            doc: DocConfig::default(),
         };
 
         config.profiles.insert("debug".to_string(), BuildProfile {
             opt_level: "0".to_string(),
             debug_info: true,
-            lto: false,
+            lto: Lto::Off,
+            lto_jobs: None,
+            split_debug: false,
+            strip: None,
+            sanitizers: vec![],
             extra_flags: vec![],
+            compiler: ProfileCompilerConfig::default(),
         });
         config.profiles.insert("release".to_string(), BuildProfile {
             opt_level: "3".to_string(),
             debug_info: false,
-            lto: true,
+            lto: Lto::Full,
+            lto_jobs: None,
+            split_debug: false,
+            strip: None,
+            sanitizers: vec![],
             extra_flags: vec!["-march=native".to_string()],
+            compiler: ProfileCompilerConfig::default(),
         });
 
         config
     }
 
+    pub fn package_name(&self) -> Option<&str> {
+        self.package.as_ref().map(|p| p.name.as_str())
+    }
+
     pub fn get_profile(&self, name: Option<&str>) -> Option<&BuildProfile> {
         name.map_or_else(
             || self.profiles.get(&self.build.default_profile),
             |n| self.profiles.get(n),
         )
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(opt_level: &str) -> BuildProfile {
+        let mut profile = Config::default_for_member("test").profiles["debug"].clone();
+        profile.opt_level = opt_level.to_string();
+        profile
+    }
+
+    #[test]
+    fn accepts_every_opt_level() {
+        for (level, msvc) in [
+            ("0", "/Od"),
+            ("1", "/O1"),
+            ("2", "/O2"),
+            ("3", "/O2"),
+            ("s", "/O1"),
+            ("z", "/O1"),
+            ("g", "/Od"),
+            ("fast", "/O2"),
+        ] {
+            for written in [level.to_string(), format!("-O{}", level), format!("O{}", level)] {
+                let mut profile = profile(&written);
+                profile.normalize_opt_level("test").unwrap();
+                assert_eq!(profile.opt_level, level, "opt_level {}", written);
+                assert_eq!(profile.msvc_opt_flag(), msvc, "opt_level {}", written);
+            }
+        }
+    }
+
+    #[test]
+    fn expands_env_in_build_and_compiler_settings() {
+        let env = |name: &str| (name == "FORGE_TEST_SDK").then(|| "/opt/sdk".to_string());
+        let mut config = Config::default_for_member("test");
+        config.build.cc = Some("${FORGE_TEST_SDK}/bin/clang".to_string());
+        config.build.cxx = Some("${FORGE_TEST_SDK}/bin/clang++".to_string());
+        config.build.compiler_wrapper = Some("${FORGE_TEST_SDK}/bin/ccache".to_string());
+        config.compiler.definitions.insert("SDK".to_string(), "\"${FORGE_TEST_SDK}\"".to_string());
+        let profile = config.profiles.get_mut("debug").unwrap();
+        profile.compiler.libraries = vec!["${FORGE_TEST_SDK}/lib/libsdk.a".to_string()];
+        profile.compiler.definitions.insert("SDK_LIB".to_string(), "${FORGE_TEST_SDK}/lib".to_string());
+        config.expand_env(&env).unwrap();
+
+        assert_eq!(config.build.cc.as_deref(), Some("/opt/sdk/bin/clang"));
+        assert_eq!(config.build.cxx.as_deref(), Some("/opt/sdk/bin/clang++"));
+        assert_eq!(config.build.compiler_wrapper.as_deref(), Some("/opt/sdk/bin/ccache"));
+        assert_eq!(config.compiler.definitions["SDK"], "\"/opt/sdk\"");
+        let profile = &config.profiles["debug"];
+        assert_eq!(profile.compiler.libraries, ["/opt/sdk/lib/libsdk.a"]);
+        assert_eq!(profile.compiler.definitions["SDK_LIB"], "/opt/sdk/lib");
+    }
+
+    #[test]
+    fn rejects_unknown_opt_level() {
+        for level in ["two", "4", "-O", ""] {
+            let err = profile(level).normalize_opt_level("release").unwrap_err();
+            let ForgeError::Config(message) = err else {
+                panic!("expected a config error for {:?}", level);
+            };
+            assert!(message.contains("Invalid opt_level"), "{}", message);
+            assert!(message.contains("profile release"), "{}", message);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    fmt::{Display, Write},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use crate::{
+    workspace::WorkspaceMember,
+    error::{ForgeError, ForgeResult},
+};
+
+/// Header extensions documented besides the member's source extensions.
+const HEADER_PATTERNS: &[&str] = &["*.h", "*.hh", "*.hpp", "*.hxx", "*.h++", "*.inl"];
+
+/// Runs Doxygen for `member` and returns the HTML index it wrote. The
+/// member's Doxyfile (`[doc] doxyfile`, or a `Doxyfile` next to its
+/// `forge.toml`) is used if there is one, otherwise the source and include
+/// directories are documented. Either way the output goes to
+/// `build/<member>/doc`.
+pub fn generate(member: &WorkspaceMember) -> ForgeResult<PathBuf> {
+    let config = &member.config.doc;
+    let output_dir = std::path::absolute(member.get_build_dir().join("doc"))
+        .map_err(|e| ForgeError::Build(format!("Failed to resolve documentation directory: {}", e)))?;
+    std::fs::create_dir_all(&output_dir)
+        .map_err(|e| ForgeError::Build(format!("Failed to create {}: {}", output_dir.display(), e)))?;
+
+    let existing = match &config.doxyfile {
+        Some(path) => Some(member.resolve_path(path)),
+        None => Some(member.path.join("Doxyfile")).filter(|path| path.exists()),
+    };
+
+    let mut doxyfile = String::new();
+    match existing {
+        Some(path) => {
+            if !path.exists() {
+                return Err(ForgeError::FileNotFound(path));
+            }
+            let _ = writeln!(doxyfile, "@INCLUDE = {}", quote(path.display()));
+        }
+        None => {
+            let mut input = Vec::new();
+            let dirs = match &config.input {
+                Some(dirs) => dirs.iter().map(|dir| member.resolve_path(dir)).collect(),
+                None => member.get_source_dirs().into_iter().chain(member.get_include_dirs()).collect::<Vec<_>>(),
+            };
+            for dir in dirs {
+                if dir.exists() && !input.contains(&dir) {
+                    input.push(dir);
+                }
+            }
+
+            let patterns = HEADER_PATTERNS.iter()
+                .map(|pattern| pattern.to_string())
+                .chain(member.config.paths.source_extensions.iter()
+                    .map(|ext| format!("*.{}", ext.strip_prefix('.').unwrap_or(ext))));
+
+            let _ = writeln!(doxyfile, "PROJECT_NAME = {}", quote(&member.name));
+            let _ = writeln!(doxyfile, "INPUT = {}", input.iter().map(|dir| quote(dir.display())).collect::<Vec<_>>().join(" "));
+            let _ = writeln!(doxyfile, "FILE_PATTERNS = {}", patterns.collect::<Vec<_>>().join(" "));
+            let _ = writeln!(doxyfile, "RECURSIVE = YES");
+            let _ = writeln!(doxyfile, "EXTRACT_ALL = YES");
+            let _ = writeln!(doxyfile, "GENERATE_LATEX = NO");
+            let _ = writeln!(doxyfile, "QUIET = YES");
+        }
+    }
+
+    for (tag, value) in &config.options {
+        let _ = writeln!(doxyfile, "{} = {}", tag, value);
+    }
+    let _ = writeln!(doxyfile, "OUTPUT_DIRECTORY = {}", quote(output_dir.display()));
+    let _ = writeln!(doxyfile, "HTML_OUTPUT = html");
+
+    let doxyfile_path = output_dir.join("Doxyfile");
+    std::fs::write(&doxyfile_path, doxyfile)
+        .map_err(|e| ForgeError::Build(format!("Failed to write {}: {}", doxyfile_path.display(), e)))?;
+
+    let status = Command::new("doxygen")
+        .arg(&doxyfile_path)
+        .current_dir(&member.path)
+        .status()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => ForgeError::Build(
+                "doxygen not found; install Doxygen (https://www.doxygen.nl) to use forge doc".to_string()
+            ),
+            _ => ForgeError::Build(format!("Failed to execute doxygen: {}", e)),
+        })?;
+    if !status.success() {
+        return Err(ForgeError::Build(format!(
+            "doxygen failed for {} with {}",
+            member.name,
+            status
+        )));
+    }
+
+    Ok(output_dir.join("html").join("index.html"))
+}
+
+/// Opens `page` in the default browser.
+pub fn open(page: &Path) -> ForgeResult<()> {
+    if !page.exists() {
+        return Err(ForgeError::FileNotFound(page.to_path_buf()));
+    }
+
+    let opener = if cfg!(windows) {
+        "cmd"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    let mut cmd = Command::new(opener);
+    if cfg!(windows) {
+        cmd.args(["/c", "start", ""]);
+    }
+
+    let status = cmd.arg(page)
+        .status()
+        .map_err(|e| ForgeError::Build(format!("Failed to open {} with {}: {}", page.display(), opener, e)))?;
+    if !status.success() {
+        return Err(ForgeError::Build(format!("Failed to open {}", page.display())));
+    }
+    Ok(())
+}
+
+/// Doxyfile values are separated by spaces unless quoted.
+fn quote(value: impl Display) -> String {
+    format!("\"{}\"", value)
+}
@@ -34,4 +34,37 @@ pub enum ForgeError {
     Toml(#[from] toml::de::Error),
 }
 
-pub type ForgeResult<T> = Result<T, ForgeError>;
\ No newline at end of file
+pub type ForgeResult<T> = Result<T, ForgeError>;
+
+impl ForgeError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ForgeError::Io(_) => "io",
+            ForgeError::InvalidTarget(_) => "invalid_target",
+            ForgeError::Config(_) => "config",
+            ForgeError::Build(_) => "build",
+            ForgeError::Compiler(_) => "compiler",
+            ForgeError::Cache(_) => "cache",
+            ForgeError::FileNotFound(_) => "file_not_found",
+            ForgeError::Workspace(_) => "workspace",
+            ForgeError::Serialization(_) => "serialization",
+            ForgeError::Toml(_) => "toml",
+        }
+    }
+
+    /// Structured form of the error for `--message-format json`.
+    pub fn to_json(&self, context: &str) -> serde_json::Value {
+        let mut value = serde_json::json!({
+            "reason": "error",
+            "kind": self.kind(),
+            "context": context,
+            "message": self.to_string(),
+        });
+
+        if let ForgeError::FileNotFound(path) = self {
+            value["file"] = serde_json::json!(path);
+        }
+
+        value
+    }
+}
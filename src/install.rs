@@ -0,0 +1,102 @@
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+use crate::{
+    config::OutputType,
+    workspace::WorkspaceMember,
+    error::{ForgeError, ForgeResult},
+};
+
+/// Copies the outputs of `members` below `prefix`: executables to `bin`,
+/// libraries to `lib` (Windows DLLs to `bin`, next to the executables
+/// loading them), and public include directories plus `[install] headers`
+/// to `include`. With `DESTDIR` set, everything is staged below it instead,
+/// as packaging tools expect.
+pub fn install(members: &[&WorkspaceMember], prefix: &Path) -> ForgeResult<()> {
+    let root = staging_root(prefix);
+
+    for member in members {
+        let target = member.get_target_path();
+        let output_type = member.config.build.output_type;
+        if output_type != OutputType::Interface && !target.exists() {
+            return Err(ForgeError::Build(format!(
+                "{} has not been built: {} not found",
+                member.name,
+                target.display()
+            )));
+        }
+
+        let file_name = target.file_name().unwrap_or_default();
+        let is_dll = target.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("dll"));
+        match output_type {
+            OutputType::Executable => copy(&target, &root.join("bin").join(file_name))?,
+            OutputType::Shared if is_dll => {
+                copy(&target, &root.join("bin").join(file_name))?;
+                let import_library = target.with_extension("lib");
+                if import_library.exists() {
+                    copy(&import_library, &root.join("lib").join(import_library.file_name().unwrap_or_default()))?;
+                }
+            }
+            OutputType::Static | OutputType::Shared => copy(&target, &root.join("lib").join(file_name))?,
+            OutputType::Interface => {}
+        }
+
+        for dir in member.get_public_include_dirs() {
+            if dir.is_dir() {
+                copy_dir(&dir, &root.join("include"))?;
+            }
+        }
+
+        for header in &member.config.install.headers {
+            let header = member.resolve_path(header);
+            copy(&header, &root.join("include").join(header.file_name().unwrap_or_default()))?;
+        }
+
+        let share = root.join("share").join(&member.config.build.target);
+        for file in &member.config.install.files {
+            let file = member.resolve_path(file);
+            copy(&file, &share.join(file.file_name().unwrap_or_default()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `prefix`, below `DESTDIR` when that is set.
+fn staging_root(prefix: &Path) -> PathBuf {
+    match std::env::var_os("DESTDIR").filter(|dir| !dir.is_empty()) {
+        Some(destdir) => {
+            let relative: PathBuf = prefix.components()
+                .filter(|c| !matches!(c, Component::Prefix(_) | Component::RootDir))
+                .collect();
+            PathBuf::from(destdir).join(relative)
+        }
+        None => prefix.to_path_buf(),
+    }
+}
+
+fn copy_dir(dir: &Path, destination: &Path) -> ForgeResult<()> {
+    for entry in WalkDir::new(dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        copy(entry.path(), &destination.join(relative))?;
+    }
+    Ok(())
+}
+
+fn copy(source: &Path, destination: &Path) -> ForgeResult<()> {
+    if !source.exists() {
+        return Err(ForgeError::FileNotFound(source.to_path_buf()));
+    }
+
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| ForgeError::Build(format!("Failed to create {}: {}", parent.display(), e)))?;
+    }
+
+    println!("Installing {}", destination.display());
+    std::fs::copy(source, destination)
+        .map_err(|e| ForgeError::Build(format!("Failed to install {}: {}", destination.display(), e)))?;
+    Ok(())
+}
@@ -0,0 +1,40 @@
+//! Forge as a library, for driving builds from other programs such as
+//! editor plugins or larger build orchestrators. The `forge` binary is a
+//! thin command line on top of it.
+//!
+//! A build loads a [`Workspace`] (a single project is a workspace of one
+//! member), sets up a [`Builder`] for it and builds the selected members:
+//!
+//! ```no_run
+//! use forge::{Builder, ForgeResult, Workspace};
+//!
+//! fn build(path: &std::path::Path) -> ForgeResult<()> {
+//!     let workspace = Workspace::new(path)?;
+//!     let builder = Builder::new(workspace.clone(), None, None, None, Some("release"))?;
+//!     let report = builder.build(&workspace.selected_members(&["app".to_string()]))?;
+//!     for member in &report.members {
+//!         println!("{}: {} compiled, {} up to date", member.name, member.compiled, member.cached);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+pub mod config;
+pub mod builder;
+mod compiler;
+pub mod workspace;
+mod cache;
+pub mod target;
+pub mod toolchains;
+pub mod error;
+pub mod messages;
+pub mod install;
+pub mod doc;
+mod ninja;
+
+pub use builder::{BuildReport, Builder, MemberReport};
+pub use config::Config;
+pub use error::{ForgeError, ForgeResult};
+pub use target::Target;
+pub use toolchains::Toolchain;
+pub use workspace::{Workspace, WorkspaceMember};
@@ -1,27 +1,45 @@
-mod config;
-mod builder;
-mod compiler;
-mod workspace;
-mod cache;
-mod target;
-mod toolchains;
-mod error;
+mod tree;
+mod watch;
 
 use std::{
     path::{Path, PathBuf},
+    str::FromStr,
     time::Instant,
 };
-use structopt::StructOpt;
-use crate::{
+use structopt::{
+    clap::{AppSettings, Shell},
+    StructOpt,
+};
+use forge::{
     builder::Builder,
-    workspace::Workspace,
+    config::{CleanMode, OutputType, Sanitizer},
+    doc,
+    install,
+    messages::{self, ColorChoice, MessageFormat, Messages},
+    target::Target,
+    workspace::{Workspace, WorkspaceMember},
     error::ForgeResult,
 };
-use crate::error::ForgeError;
+use forge::error::ForgeError;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "forge", about = "A fast C/C++ build system with cross-compilation support")]
-enum Forge {
+struct Forge {
+    #[structopt(long = "message-format", global = true, default_value = "human", possible_values = &["human", "json"], help = "Output format for messages (human/json)")]
+    message_format: MessageFormat,
+
+    #[structopt(long = "color", global = true, default_value = "auto", possible_values = &["auto", "always", "never"], help = "Color output (auto colors terminals unless NO_COLOR is set)")]
+    color: ColorChoice,
+
+    #[structopt(short = "v", long = "verbose", global = true, parse(from_occurrences), help = "Print every command before running it (-vv also shows include resolution)")]
+    verbose: u8,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
     #[structopt(name = "build", about = "Build projects")]
     Build {
         #[structopt(long, parse(from_os_str), help = "Path to workspace or project")]
@@ -42,11 +60,62 @@ enum Forge {
         #[structopt(long = "sysroot", parse(from_os_str), help = "Path to sysroot")]
         sysroot: Option<PathBuf>,
 
-        #[structopt(long = "profile", help = "Build profile (debug/release)")]
-        profile: Option<String>,
+        #[structopt(long = "profile", number_of_values = 1, help = "Build profile (debug/release), repeatable to build several profiles")]
+        profile: Vec<String>,
 
         #[structopt(long = "release", help = "Build with release profile")]
         release: bool,
+
+        #[structopt(long = "warnings-as-errors", conflicts_with = "no-warnings-as-errors", help = "Treat compiler warnings as errors")]
+        warnings_as_errors: bool,
+
+        #[structopt(long = "no-warnings-as-errors", help = "Do not treat compiler warnings as errors")]
+        no_warnings_as_errors: bool,
+
+        #[structopt(long = "explain-cache", parse(from_os_str), help = "Explain the cache decision for a source file instead of building")]
+        explain_cache: Option<PathBuf>,
+
+        #[structopt(long = "build-dir", parse(from_os_str), help = "Directory for build outputs, instead of the configured build path")]
+        build_dir: Option<PathBuf>,
+
+        #[structopt(long = "cache-dir", parse(from_os_str), help = "Directory for the build cache (defaults to <build-dir>/.forge_cache)")]
+        cache_dir: Option<PathBuf>,
+
+        #[structopt(long = "force", help = "Rebuild everything, ignoring the build cache")]
+        force: bool,
+
+        #[structopt(long = "force-member", number_of_values = 1, help = "Rebuild every file of a workspace member, ignoring the build cache")]
+        force_member: Vec<String>,
+
+        #[structopt(long = "force-file", number_of_values = 1, parse(from_os_str), help = "Rebuild a source file, ignoring the build cache")]
+        force_file: Vec<PathBuf>,
+
+        #[structopt(long = "no-quick-check", help = "Detect changed files by content hash instead of mtime and size")]
+        no_quick_check: bool,
+
+        #[structopt(long = "timings", help = "Report the slowest files and per-member build times")]
+        timings: bool,
+
+        #[structopt(long = "no-link", alias = "emit-objects-only", help = "Compile every source without linking")]
+        no_link: bool,
+
+        #[structopt(short = "k", long = "keep-going", help = "Compile every source and build every member whose dependencies built, even after failures")]
+        keep_going: bool,
+
+        #[structopt(long = "strip", conflicts_with = "no-strip", help = "Strip symbols from linked outputs")]
+        strip: bool,
+
+        #[structopt(long = "no-strip", help = "Keep symbols in linked outputs")]
+        no_strip: bool,
+
+        #[structopt(long = "sanitize", use_delimiter = true, help = "Sanitizers to build with, instead of the profile's (address, undefined, thread, memory, leak)")]
+        sanitize: Vec<Sanitizer>,
+
+        #[structopt(long = "size", help = "Report the text, data and bss sizes of linked outputs")]
+        size: bool,
+
+        #[structopt(short = "q", long = "quiet", help = "Only print errors, not progress or compiler warnings")]
+        quiet: bool,
     },
 
     #[structopt(name = "init", about = "Initialize a new project or workspace")]
@@ -64,6 +133,21 @@ enum Forge {
         target: Option<String>,
     },
 
+    #[structopt(name = "add", about = "Add a new member to the workspace")]
+    Add {
+        #[structopt(help = "Directory of the new member, relative to the workspace root")]
+        name: String,
+
+        #[structopt(long, parse(from_os_str), help = "Path to the workspace root")]
+        path: Option<PathBuf>,
+
+        #[structopt(long, conflicts_with = "bin", help = "Create a static library member")]
+        lib: bool,
+
+        #[structopt(long, help = "Create an executable member (default)")]
+        bin: bool,
+    },
+
     #[structopt(name = "clean", about = "Clean build artifacts")]
     Clean {
         #[structopt(long, parse(from_os_str), help = "Path to workspace or project")]
@@ -71,45 +155,238 @@ enum Forge {
 
         #[structopt(long, help = "Specific workspace members to clean")]
         members: Vec<String>,
+
+        #[structopt(long = "build-dir", parse(from_os_str), help = "Directory for build outputs, instead of the configured build path")]
+        build_dir: Option<PathBuf>,
+
+        #[structopt(long = "cache-dir", parse(from_os_str), help = "Directory for the build cache (defaults to <build-dir>/.forge_cache)")]
+        cache_dir: Option<PathBuf>,
+
+        #[structopt(long = "cache-only", conflicts_with = "artifacts-only", help = "Only clear the build cache, keeping build outputs")]
+        cache_only: bool,
+
+        #[structopt(long = "artifacts-only", help = "Only remove build outputs, keeping the build cache")]
+        artifacts_only: bool,
+
+        #[structopt(long, conflicts_with_all = &["cache-only", "artifacts-only"], help = "Only remove objects and cache entries of deleted sources")]
+        prune: bool,
     },
 
+    #[structopt(name = "watch", about = "Rebuild whenever sources change")]
+    Watch(WatchArgs),
+
     #[structopt(name = "run", about = "Build and run the project")]
-    Run {
-        #[structopt(long, parse(from_os_str), help = "Path to workspace or project")]
-        path: Option<PathBuf>,
+    Run(RunArgs),
 
-        #[structopt(long, help = "Specific workspace member to run")]
-        member: Option<String>,
+    #[structopt(name = "test", about = "Run project tests")]
+    Test(TestArgs),
 
-        #[structopt(long = "release", help = "Run with release profile")]
-        release: bool,
+    #[structopt(name = "install", about = "Build and install binaries, libraries and headers")]
+    Install(InstallArgs),
+
+    #[structopt(name = "doc", about = "Generate documentation with Doxygen")]
+    Doc(DocArgs),
 
-        #[structopt(long = "profile", help = "Build profile (debug/release)")]
-        profile: Option<String>,
+    #[structopt(name = "generate", about = "Generate build files for another build tool")]
+    Generate(GenerateArgs),
 
-        #[structopt(name = "args", last = true)]
-        args: Vec<String>,
+    #[structopt(name = "tree", about = "Print the workspace dependency graph")]
+    Tree(TreeArgs),
+
+    #[structopt(name = "completions", about = "Print a shell completion script", setting = AppSettings::Hidden)]
+    Completions {
+        #[structopt(possible_values = &Shell::variants(), case_insensitive = true, help = "Shell to generate completions for")]
+        shell: Shell,
     },
+}
 
-    #[structopt(name = "test", about = "Run project tests")]
-    Test {
-        #[structopt(long, parse(from_os_str), help = "Path to workspace or project")]
-        path: Option<PathBuf>,
+#[derive(Debug, StructOpt)]
+struct WatchArgs {
+    #[structopt(long, parse(from_os_str), help = "Path to workspace or project")]
+    path: Option<PathBuf>,
 
-        #[structopt(long, help = "Specific workspace member to test")]
-        member: Option<String>,
+    #[structopt(long, help = "Specific workspace members to build")]
+    members: Vec<String>,
 
-        #[structopt(long = "release", help = "Test with release profile")]
-        release: bool,
+    #[structopt(long = "release", help = "Build with release profile")]
+    release: bool,
+
+    #[structopt(long = "profile", help = "Build profile (debug/release)")]
+    profile: Option<String>,
 
-        #[structopt(long = "profile", help = "Build profile (debug/release)")]
-        profile: Option<String>,
+    #[structopt(long = "build-dir", parse(from_os_str), help = "Directory for build outputs, instead of the configured build path")]
+    build_dir: Option<PathBuf>,
 
-        #[structopt(name = "args", last = true)]
-        args: Vec<String>,
+    #[structopt(long = "cache-dir", parse(from_os_str), help = "Directory for the build cache (defaults to <build-dir>/.forge_cache)")]
+    cache_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+struct RunArgs {
+    #[structopt(long, parse(from_os_str), help = "Path to workspace or project")]
+    path: Option<PathBuf>,
+
+    #[structopt(long, help = "Specific workspace member to run")]
+    member: Option<String>,
+
+    #[structopt(long = "release", help = "Run with release profile")]
+    release: bool,
+
+    #[structopt(long = "profile", help = "Build profile (debug/release)")]
+    profile: Option<String>,
+
+    #[structopt(long = "target", help = "Target triple for cross-compilation")]
+    target: Option<String>,
+
+    #[structopt(long = "toolchain", help = "Path to cross-compilation toolchain")]
+    toolchain: Option<String>,
+
+    #[structopt(long = "sysroot", parse(from_os_str), help = "Path to sysroot")]
+    sysroot: Option<PathBuf>,
+
+    #[structopt(long = "build-dir", parse(from_os_str), help = "Directory for build outputs, instead of the configured build path")]
+    build_dir: Option<PathBuf>,
+
+    #[structopt(long = "cache-dir", parse(from_os_str), help = "Directory for the build cache (defaults to <build-dir>/.forge_cache)")]
+    cache_dir: Option<PathBuf>,
+
+    #[structopt(long = "no-build", help = "Run the previously built binary without building")]
+    no_build: bool,
+
+    #[structopt(long, parse(from_os_str), help = "Working directory of the program (defaults to [run] cwd, or the current directory)")]
+    cwd: Option<PathBuf>,
+
+    #[structopt(long = "env", number_of_values = 1, parse(try_from_str = parse_env), help = "Environment variable for the program as KEY=VALUE (repeatable)")]
+    env: Vec<(String, String)>,
+
+    #[structopt(name = "args", last = true)]
+    args: Vec<String>,
+}
+
+fn parse_env(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("Expected KEY=VALUE, got '{}'", s)),
     }
 }
 
+#[derive(Debug, StructOpt)]
+struct TestArgs {
+    #[structopt(long, parse(from_os_str), help = "Path to workspace or project")]
+    path: Option<PathBuf>,
+
+    #[structopt(long, help = "Specific workspace member to test")]
+    member: Option<String>,
+
+    #[structopt(long = "release", help = "Test with release profile")]
+    release: bool,
+
+    #[structopt(long = "profile", help = "Build profile (debug/release)")]
+    profile: Option<String>,
+
+    #[structopt(long = "target", help = "Target triple for cross-compilation")]
+    target: Option<String>,
+
+    #[structopt(long = "toolchain", help = "Path to cross-compilation toolchain")]
+    toolchain: Option<String>,
+
+    #[structopt(long = "sysroot", parse(from_os_str), help = "Path to sysroot")]
+    sysroot: Option<PathBuf>,
+
+    #[structopt(long = "build-dir", parse(from_os_str), help = "Directory for build outputs, instead of the configured build path")]
+    build_dir: Option<PathBuf>,
+
+    #[structopt(long = "cache-dir", parse(from_os_str), help = "Directory for the build cache (defaults to <build-dir>/.forge_cache)")]
+    cache_dir: Option<PathBuf>,
+
+    #[structopt(long = "no-build", help = "Run the previously built tests without building")]
+    no_build: bool,
+
+    #[structopt(long = "no-fail-fast", help = "Run every test binary, even after one failed")]
+    no_fail_fast: bool,
+
+    #[structopt(name = "args", last = true)]
+    args: Vec<String>,
+}
+
+#[derive(Debug, StructOpt)]
+struct InstallArgs {
+    #[structopt(long, parse(from_os_str), help = "Path to workspace or project")]
+    path: Option<PathBuf>,
+
+    #[structopt(long, help = "Specific workspace members to install")]
+    members: Vec<String>,
+
+    #[structopt(long, parse(from_os_str), default_value = "/usr/local", help = "Installation prefix")]
+    prefix: PathBuf,
+
+    #[structopt(long = "release", help = "Install the release build")]
+    release: bool,
+
+    #[structopt(long = "profile", help = "Build profile (debug/release)")]
+    profile: Option<String>,
+
+    #[structopt(long = "build-dir", parse(from_os_str), help = "Directory for build outputs, instead of the configured build path")]
+    build_dir: Option<PathBuf>,
+
+    #[structopt(long = "cache-dir", parse(from_os_str), help = "Directory for the build cache (defaults to <build-dir>/.forge_cache)")]
+    cache_dir: Option<PathBuf>,
+
+    #[structopt(long = "no-build", help = "Install the previous build without building")]
+    no_build: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct DocArgs {
+    #[structopt(long, parse(from_os_str), help = "Path to workspace or project")]
+    path: Option<PathBuf>,
+
+    #[structopt(long, help = "Specific workspace members to document")]
+    members: Vec<String>,
+
+    #[structopt(long = "build-dir", parse(from_os_str), help = "Directory for build outputs, instead of the configured build path")]
+    build_dir: Option<PathBuf>,
+
+    #[structopt(long, help = "Open the generated HTML in a browser")]
+    open: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct GenerateArgs {
+    #[structopt(possible_values = &["ninja"], help = "Build file format")]
+    format: String,
+
+    #[structopt(long, parse(from_os_str), help = "Path to workspace or project")]
+    path: Option<PathBuf>,
+
+    #[structopt(long, help = "Specific workspace members to build")]
+    members: Vec<String>,
+
+    #[structopt(long = "release", help = "Generate for the release profile")]
+    release: bool,
+
+    #[structopt(long = "profile", help = "Build profile (debug/release)")]
+    profile: Option<String>,
+
+    #[structopt(long = "target", help = "Target triple (e.g., x86_64-unknown-linux-gnu)")]
+    target: Option<String>,
+
+    #[structopt(long = "build-dir", parse(from_os_str), help = "Directory for build outputs, instead of the configured build path")]
+    build_dir: Option<PathBuf>,
+
+    #[structopt(short = "o", long = "output", parse(from_os_str), help = "File to write (defaults to build.ninja in the build directory)")]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+struct TreeArgs {
+    #[structopt(long, parse(from_os_str), help = "Path to workspace or project")]
+    path: Option<PathBuf>,
+
+    #[structopt(long, default_value = "text", possible_values = &["text", "dot"], help = "Output format (an indented tree, or Graphviz dot)")]
+    format: String,
+}
+
 fn init_project(
     path: &Path,
     is_workspace: bool,
@@ -123,13 +400,13 @@ fn init_project(
             .unwrap_or("project")
     });
 
-    let native_target = format!("{}-unknown-{}",
-                                std::env::consts::ARCH,
-                                match std::env::consts::OS {
-                                    "macos" => "darwin",
-                                    os => os
-                                }
-    );
+    // Native projects need no [cross] section, and an empty toolchain or
+    // sysroot in one would only point the build at nothing
+    let native_target = Target::host()?.to_string();
+    let cross = match target {
+        Some(target) if target != native_target => format!("\n[cross]\ntarget = \"{}\"\n", target),
+        _ => String::new(),
+    };
 
     let default_compiler = match std::env::consts::OS {
         "windows" => "cl.exe",
@@ -181,13 +458,7 @@ build = "build"
 [build]
 compiler = "{compiler}"
 target = "{name}"
-
-[cross]
-target = "{target}"
-toolchain = ""
-sysroot = ""
-extra_flags = []
-
+{cross}
 [profiles.debug]
 opt_level = "0"
 debug_info = true
@@ -211,7 +482,7 @@ warnings_as_errors = true
 library_paths = []
 libraries = []
 "#,
-            target = target.unwrap_or(&native_target),
+            cross = cross,
             compiler = default_compiler
         )
     };
@@ -226,6 +497,7 @@ int main()
     std::cout << "Hello from Forge!" << std::endl;
     return 0;
 }
+
 "#;
     std::fs::write(path.join("src").join("main.cpp"), example_src)?;
 
@@ -248,33 +520,158 @@ public:
     Ok(())
 }
 
-fn run_project(
-    path: Option<PathBuf>,
-    member: Option<String>,
-    args: Vec<String>,
-    profile: Option<String>,
-    release: bool,
-) -> ForgeResult<()> {
-    let path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
-    let profile = if release {
+/// Creates the directory and `forge.toml` of a new workspace member and
+/// lists it in the root's `[workspace] members`, keeping the rest of the
+/// root config as it is.
+fn add_member(root: &Path, name: &str, output_type: OutputType) -> ForgeResult<()> {
+    let root_config_path = root.join("forge.toml");
+    let content = std::fs::read_to_string(&root_config_path)
+        .map_err(|_| ForgeError::FileNotFound(root_config_path.clone()))?;
+    let mut document: toml_edit::DocumentMut = content.parse()
+        .map_err(|e| ForgeError::Config(format!("Failed to parse {}: {}", root_config_path.display(), e)))?;
+
+    let member_path = root.join(name);
+    if member_path.exists() {
+        return Err(ForgeError::Workspace(format!("{} already exists", member_path.display())));
+    }
+
+    let workspace = document.entry("workspace")
+        .or_insert_with(toml_edit::table)
+        .as_table_mut()
+        .ok_or_else(|| ForgeError::Config("[workspace] is not a table".to_string()))?;
+    let members = workspace.entry("members")
+        .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+        .as_array_mut()
+        .ok_or_else(|| ForgeError::Config("[workspace] members is not an array".to_string()))?;
+    if members.iter().any(|member| member.as_str() == Some(name)) {
+        return Err(ForgeError::Workspace(format!("{} is already a workspace member", name)));
+    }
+    // Keep one-member-per-line lists that way
+    let prefix = members.iter().last()
+        .and_then(|last| last.decor().prefix().cloned())
+        .filter(|prefix| prefix.as_str().is_some_and(|p| p.contains('\n')));
+    members.push(name);
+    if let (Some(prefix), Some(added)) = (prefix, members.iter_mut().last()) {
+        added.decor_mut().set_prefix(prefix);
+    }
+
+    let target = Path::new(name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| ForgeError::Workspace(format!("Invalid member name: {}", name)))?;
+    let output_type_name = match output_type {
+        OutputType::Static => "static",
+        OutputType::Shared => "shared",
+        OutputType::Executable => "executable",
+        OutputType::Interface => "interface",
+    };
+
+    let config = format!(
+        r#"[build]
+compiler = "g++"
+target = "{target}"
+output_type = "{output_type_name}"
+
+[compiler]
+flags = ["-Wall", "-std=c++17"]
+
+[paths]
+src = "src"
+include = ["include"]
+
+[profiles.debug]
+opt_level = "0"
+debug_info = true
+lto = false
+
+[profiles.release]
+opt_level = "3"
+debug_info = false
+lto = true
+extra_flags = ["-march=native"]
+"#
+    );
+
+    std::fs::create_dir_all(member_path.join("src"))?;
+    std::fs::create_dir_all(member_path.join("include"))?;
+    std::fs::write(member_path.join("forge.toml"), config)?;
+
+    if output_type == OutputType::Executable {
+        std::fs::write(member_path.join("src").join("main.cpp"), "int main()\n{\n    return 0;\n}\n")?;
+    } else {
+        std::fs::write(member_path.join("include").join(format!("{}.hpp", target)), "#pragma once\n")?;
+        std::fs::write(
+            member_path.join("src").join(format!("{}.cpp", target)),
+            format!("#include \"{}.hpp\"\n", target),
+        )?;
+    }
+
+    std::fs::write(root_config_path, document.to_string())?;
+    println!("Added {} to the workspace", name);
+    Ok(())
+}
+
+fn watch_project(opts: WatchArgs, verbosity: u8) -> ForgeResult<()> {
+    let path = opts.path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    let profile = if opts.release {
         Some("release".to_string())
     } else {
-        profile
+        opts.profile
     };
 
-    let workspace = Workspace::new(&path)?;
-    let builder = Builder::new(
+    let mut workspace = Workspace::new(&path)?;
+    workspace.set_build_dir(opts.build_dir);
+    workspace.set_profile(profile.clone());
+    let mut builder = Builder::new(
         workspace.clone(),
         None,
         None,
         None,
         profile.as_deref(),
-    );
+    )?;
+    if let Some(dir) = &opts.cache_dir {
+        builder.set_cache_dir(dir);
+    }
+    builder.set_verbosity(verbosity);
 
-    let members = if let Some(member_name) = member {
+    let members = workspace.selected_members(&opts.members);
+    if members.is_empty() {
+        return Err(ForgeError::Workspace("No matching workspace member found".to_string()));
+    }
+
+    watch::watch(&builder, &workspace, &members)
+}
+
+fn run_project(opts: RunArgs, verbosity: u8) -> ForgeResult<()> {
+    let path = opts.path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    let profile = if opts.release {
+        Some("release".to_string())
+    } else {
+        opts.profile
+    };
+
+    let mut workspace = Workspace::new(&path)?;
+    workspace.set_build_dir(opts.build_dir);
+    workspace.set_profile(profile.clone());
+    workspace.set_target(opts.target.clone());
+    let mut builder = Builder::new(
+        workspace.clone(),
+        opts.target.as_deref(),
+        opts.toolchain.as_deref(),
+        opts.sysroot.as_deref(),
+        profile.as_deref(),
+    )?;
+    if let Some(dir) = &opts.cache_dir {
+        builder.set_cache_dir(dir);
+    }
+    builder.set_verbosity(verbosity);
+
+    let members = if let Some(member_name) = opts.member {
         workspace.filter_members(&[member_name])
-    } else if !workspace.root_config.build.target.is_empty() {
-        workspace.filter_members(&["root".to_string()])
+    } else if let Some(member) = workspace.default_member() {
+        vec![member]
+    } else if let Some(root) = workspace.root_member() {
+        vec![root]
     } else if workspace.members.len() == 1 {
         workspace.filter_members(&[])
     } else {
@@ -287,12 +684,53 @@ fn run_project(
         return Err(ForgeError::Workspace("No matching workspace member found".to_string()));
     }
 
-    builder.build(&members)?;
+    if members[0].config.build.output_type != OutputType::Executable {
+        return Err(ForgeError::Build(format!(
+            "{} is a library and cannot be run",
+            members[0].name
+        )));
+    }
+
+    if !opts.no_build {
+        builder.build(&members)?;
+    }
+
+    if let (Some(triple), None) = (members[0].target_triple(), &members[0].config.run.runner) {
+        let target = Target::from_str(triple)?;
+        let host = Target::host()?;
+        if !target.runs_on(&host) {
+            messages::warning(format_args!(
+                "running a binary built for {} on host {}",
+                target, host
+            ));
+        }
+    }
 
     let target = &members[0].get_target_path();
-    let status = std::process::Command::new(target)
-        .args(args)
-        .status()
+    if opts.no_build && !target.exists() {
+        return Err(ForgeError::Build(format!(
+            "No binary found at {}; build it first or run without --no-build",
+            target.display()
+        )));
+    }
+
+    let member = members[0];
+    let run_config = &member.config.run;
+    let args = if opts.args.is_empty() { &run_config.args } else { &opts.args };
+    let cwd = opts.cwd.or_else(|| run_config.cwd.as_ref().map(|dir| member.resolve_path(dir)));
+
+    // The target path may be relative to the current directory
+    let target = &target.canonicalize()
+        .map_err(|e| ForgeError::Build(format!("Failed to execute {}: {}", target.display(), e)))?;
+    let mut command = program_command(member, target);
+    command.args(args)
+        .envs(&run_config.env)
+        .envs(opts.env);
+    if let Some(cwd) = &cwd {
+        command.current_dir(cwd);
+    }
+
+    let status = command.status()
         .map_err(|e| ForgeError::Build(format!("Failed to execute {}: {}", target.display(), e)))?;
 
     if !status.success() {
@@ -305,26 +743,129 @@ fn run_project(
     Ok(())
 }
 
-fn run_tests(
-    path: Option<PathBuf>,
-    member: Option<String>,
-    args: Vec<String>,
-    profile: Option<String>,
-    release: bool,
-) -> ForgeResult<()> {
-    let path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
-    let profile = if release {
+fn install_project(opts: InstallArgs, verbosity: u8) -> ForgeResult<()> {
+    let path = opts.path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    let profile = if opts.release {
+        Some("release".to_string())
+    } else {
+        opts.profile
+    };
+
+    let mut workspace = Workspace::new(&path)?;
+    workspace.set_build_dir(opts.build_dir);
+    workspace.set_profile(profile.clone());
+    let mut builder = Builder::new(
+        workspace.clone(),
+        None,
+        None,
+        None,
+        profile.as_deref(),
+    )?;
+    if let Some(dir) = &opts.cache_dir {
+        builder.set_cache_dir(dir);
+    }
+    builder.set_verbosity(verbosity);
+
+    let members = workspace.selected_members(&opts.members);
+    if members.is_empty() {
+        return Err(ForgeError::Workspace("No matching workspace member found".to_string()));
+    }
+
+    if !opts.no_build {
+        builder.build(&members)?;
+    }
+
+    install::install(&members, &opts.prefix)
+}
+
+fn document_project(opts: DocArgs) -> ForgeResult<()> {
+    let path = opts.path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    let mut workspace = Workspace::new(&path)?;
+    workspace.set_build_dir(opts.build_dir);
+
+    let members = workspace.selected_members(&opts.members);
+    if members.is_empty() {
+        return Err(ForgeError::Workspace("No matching workspace member found".to_string()));
+    }
+
+    for member in members {
+        println!("Documenting {}", member.name);
+        let index = doc::generate(member)?;
+        println!("Wrote {}", index.display());
+        if opts.open {
+            doc::open(&index)?;
+        }
+    }
+    Ok(())
+}
+
+fn generate_build_file(opts: GenerateArgs) -> ForgeResult<()> {
+    let path = opts.path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    let profile = if opts.release {
         Some("release".to_string())
     } else {
-        profile
+        opts.profile
     };
 
-    let workspace = Workspace::new(&path)?;
+    let mut workspace = Workspace::new(&path)?;
+    workspace.set_build_dir(opts.build_dir);
+    workspace.set_profile(profile.clone());
+    let mut builder = Builder::new(
+        workspace.clone(),
+        opts.target.as_deref(),
+        None,
+        None,
+        profile.as_deref(),
+    )?;
+    // Compiler colors depend on forge's terminal, not ninja's
+    builder.set_message_format(MessageFormat::Human, true);
+
+    let members = workspace.selected_members(&opts.members);
+    let output = match opts.format.as_str() {
+        "ninja" => {
+            let output = opts.output.unwrap_or_else(|| workspace.build_root().join("build.ninja"));
+            builder.write_ninja(&members, &output)?;
+            output
+        }
+        format => return Err(ForgeError::Config(format!("Unknown build file format: {}", format))),
+    };
+    println!("Wrote {}", output.display());
+    Ok(())
+}
+
+/// A command running `program`, through the member's `[run] runner` when it
+/// has one.
+fn program_command(member: &WorkspaceMember, program: &Path) -> std::process::Command {
+    let mut runner = member.config.run.runner.iter().flat_map(|runner| runner.split_whitespace());
+    match runner.next() {
+        Some(runner_program) => {
+            let mut command = std::process::Command::new(runner_program);
+            command.args(runner).arg(program);
+            command
+        }
+        None => std::process::Command::new(program),
+    }
+}
+
+fn run_tests(opts: TestArgs, verbosity: u8) -> ForgeResult<()> {
+    let path = opts.path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    let profile = if opts.release {
+        Some("release".to_string())
+    } else {
+        opts.profile
+    };
+
+    let mut workspace = Workspace::new(&path)?;
+    workspace.set_build_dir(opts.build_dir);
+    workspace.set_profile(profile.clone());
+    workspace.set_target(opts.target.clone());
     let member = {
-        let members = if let Some(member_name) = member {
+        let members = if let Some(member_name) = opts.member {
             workspace.filter_members(&[member_name])
-        } else if !workspace.root_config.build.target.is_empty() {
-            workspace.filter_members(&["root".to_string()])
+        } else if let Some(member) = workspace.default_member() {
+            vec![member]
+        } else if let Some(root) = workspace.root_member() {
+            vec![root]
         } else if workspace.members.len() == 1 {
             workspace.filter_members(&[])
         } else {
@@ -343,41 +884,96 @@ fn run_tests(
     let test_config = member.config.testing.as_ref()
         .ok_or_else(|| ForgeError::Config("No test configuration found".to_string()))?;
 
-    let builder = Builder::new(
+    let mut builder = Builder::new(
         workspace,
-        None,
-        None,
-        None,
+        opts.target.as_deref(),
+        opts.toolchain.as_deref(),
+        opts.sysroot.as_deref(),
         profile.as_deref(),
-    );
+    )?;
+    if let Some(dir) = &opts.cache_dir {
+        builder.set_cache_dir(dir);
+    }
+    builder.set_verbosity(verbosity);
+
+    if !opts.no_build {
+        builder.build_tests(&member, test_config)?;
+    }
 
-    builder.build_tests(&member, test_config)?;
+    let test_binaries = builder.test_binaries(&member, test_config)?;
+    if opts.no_build {
+        if let Some(missing) = test_binaries.iter().find(|binary| !binary.exists()) {
+            return Err(ForgeError::Build(format!(
+                "No test binary found at {}; build it first or run without --no-build",
+                missing.display()
+            )));
+        }
+    }
 
-    let test_binary = &member.get_target_path();
     println!("Running tests...");
 
-    let status = std::process::Command::new(test_binary)
-        .args(args)
-        .status()
-        .map_err(|e| ForgeError::Build(format!("Failed to execute tests: {}", e)))?;
+    let mut passed = 0;
+    let mut failed = Vec::new();
+    for test_binary in &test_binaries {
+        if test_binaries.len() > 1 {
+            println!("Running {}", test_binary.display());
+        }
 
-    if !status.success() {
-        return Err(ForgeError::Build(format!(
-            "Tests failed with code {}",
-            status.code().unwrap_or(-1)
-        )));
+        let status = program_command(&member, test_binary)
+            .args(&opts.args)
+            .status()
+            .map_err(|e| ForgeError::Build(format!("Failed to execute tests: {}", e)))?;
+
+        if status.success() {
+            passed += 1;
+        } else {
+            failed.push((test_binary, status.code().unwrap_or(-1)));
+            if !opts.no_fail_fast {
+                break;
+            }
+        }
     }
 
-    println!("All tests passed!");
-    Ok(())
+    let not_run = test_binaries.len() - passed - failed.len();
+    if not_run > 0 {
+        println!("{} passed, {} failed, {} not run", passed, failed.len(), not_run);
+    } else {
+        println!("{} passed, {} failed", passed, failed.len());
+    }
+
+    match failed.as_slice() {
+        [] => Ok(()),
+        [(_, code)] if test_binaries.len() == 1 => {
+            Err(ForgeError::Build(format!("Tests failed with code {}", code)))
+        }
+        failed => {
+            let failures: Vec<String> = failed.iter()
+                .map(|(binary, code)| format!("  {} (code {})", binary.display(), code))
+                .collect();
+            Err(ForgeError::Build(format!(
+                "{} of {} test binaries failed:\n{}",
+                failed.len(),
+                test_binaries.len(),
+                failures.join("\n")
+            )))
+        }
+    }
+}
+
+fn fail(format: MessageFormat, context: &str, error: ForgeError) -> ! {
+    Messages::new(format).error(context, &error);
+    std::process::exit(1)
 }
 
 fn main() {
     env_logger::init();
 
     let opt = Forge::from_args();
-    match opt {
-        Forge::Build {
+    messages::set_color_choice(opt.color);
+    let format = opt.message_format;
+    let verbose = opt.verbose;
+    match opt.command {
+        Command::Build {
             path,
             members,
             jobs,
@@ -386,91 +982,211 @@ fn main() {
             sysroot,
             profile,
             release,
+            warnings_as_errors,
+            no_warnings_as_errors,
+            explain_cache,
+            build_dir,
+            cache_dir,
+            force,
+            force_member,
+            force_file,
+            no_quick_check,
+            timings,
+            no_link,
+            keep_going,
+            strip,
+            no_strip,
+            sanitize,
+            size,
+            quiet,
         } => {
             let start = Instant::now();
-
-            if let Some(n) = jobs {
-                rayon::ThreadPoolBuilder::new()
-                    .num_threads(n)
-                    .build_global()
-                    .unwrap();
-            }
+            let messages = Messages::new(format).quiet(quiet);
 
             let path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
 
-            let profile = if release {
-                Some("release".to_string())
-            } else {
-                profile
-            };
+            let mut profiles = profile;
+            if release && !profiles.iter().any(|p| p == "release") {
+                profiles.push("release".to_string());
+            }
 
             match Workspace::new(&path) {
-                Ok(workspace) => {
+                Ok(mut workspace) => {
+                    workspace.set_build_dir(build_dir);
                     let workspace_clone = workspace.clone();
-                    let filtered_members = workspace_clone.filter_members(&members);
-                    let builder = Builder::new(
+                    let filtered_members = workspace_clone.selected_members(&members);
+                    let mut builder = match Builder::new(
                         workspace,
                         target.as_deref(),
                         toolchain.as_deref(),
                         sysroot.as_deref(),
-                        profile.as_deref(),
-                    );
+                        profiles.first().map(String::as_str),
+                    ) {
+                        Ok(builder) => builder,
+                        Err(e) => fail(format, "Build failed", e),
+                    };
+
+                    if let Some(dir) = &cache_dir {
+                        builder.set_cache_dir(dir);
+                    }
 
-                    if let Err(e) = builder.build(&filtered_members) {
-                        eprintln!("Build failed: {}", e);
-                        std::process::exit(1);
+                    builder.set_jobs(jobs);
+                    builder.set_verbosity(verbose);
+                    builder.set_message_format(format, quiet);
+                    builder.set_timings(timings);
+                    builder.set_size_report(size);
+                    builder.set_no_link(no_link);
+                    builder.set_keep_going(keep_going);
+                    if strip {
+                        builder.set_strip(Some(true));
+                    } else if no_strip {
+                        builder.set_strip(Some(false));
                     }
-                    println!("Build completed in {:.2}s", start.elapsed().as_secs_f32());
-                }
-                Err(e) => {
-                    eprintln!("Failed to load workspace: {}", e);
-                    std::process::exit(1);
+                    if !sanitize.is_empty() {
+                        builder.set_sanitizers(Some(sanitize));
+                    }
+                    builder.set_force(force, force_member, force_file);
+
+                    if no_quick_check {
+                        builder.set_quick_check(false);
+                    }
+
+                    if warnings_as_errors {
+                        builder.set_warnings_as_errors(Some(true));
+                    } else if no_warnings_as_errors {
+                        builder.set_warnings_as_errors(Some(false));
+                    }
+
+                    if let Some(file) = explain_cache {
+                        match builder.explain_cache(&file) {
+                            Ok(explanation) => print!("{}", explanation),
+                            Err(e) => fail(format, "Explain failed", e),
+                        }
+                        return;
+                    }
+
+                    if profiles.len() <= 1 {
+                        if let Err(e) = builder.build(&filtered_members) {
+                            fail(format, "Build failed", e);
+                        }
+                    } else {
+                        for profile in profiles {
+                            messages.status(format_args!("Building profile {}", profile));
+                            builder.set_profile(Some(profile));
+                            if let Err(e) = builder.build(&filtered_members) {
+                                fail(format, "Build failed", e);
+                            }
+                        }
+                    }
+                    messages.status(format_args!("Build completed in {:.2}s", start.elapsed().as_secs_f32()));
                 }
+                Err(e) => fail(format, "Failed to load workspace", e),
             }
         }
 
-        Forge::Init { path, workspace, name, target } => {
+        Command::Init { path, workspace, name, target } => {
             let path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
             if let Err(e) = init_project(&path, workspace, name.as_deref(), target.as_deref()) {
-                eprintln!("Failed to initialize project: {}", e);
-                std::process::exit(1);
+                fail(format, "Failed to initialize project", e);
             }
         }
 
-        Forge::Clean { path, members } => {
+        Command::Add { name, path, lib, bin } => {
+            let path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
+            let output_type = if lib && !bin { OutputType::Static } else { OutputType::Executable };
+            if let Err(e) = add_member(&path, &name, output_type) {
+                fail(format, "Failed to add member", e);
+            }
+        }
+
+        Command::Clean { path, members, build_dir, cache_dir, cache_only, artifacts_only, prune } => {
             let path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
             match Workspace::new(&path) {
-                Ok(workspace) => {
+                Ok(mut workspace) => {
+                    workspace.set_build_dir(build_dir);
                     let workspace_clone = workspace.clone();
                     let filtered_members = workspace_clone.filter_members(&members);
-                    let builder = Builder::new(
+                    let mode = if cache_only {
+                        CleanMode::Cache
+                    } else if artifacts_only {
+                        CleanMode::Artifacts
+                    } else {
+                        workspace.root_config.build.clean_mode
+                    };
+                    let mut builder = match Builder::new(
                         workspace,
                         None,
                         None,
                         None,
                         None,
-                    );
-                    if let Err(e) = builder.clean(&filtered_members) {
-                        eprintln!("Clean failed: {}", e);
-                        std::process::exit(1);
+                    ) {
+                        Ok(builder) => builder,
+                        Err(e) => fail(format, "Clean failed", e),
+                    };
+                    if let Some(dir) = &cache_dir {
+                        builder.set_cache_dir(dir);
+                    }
+                    builder.set_message_format(format, false);
+                    let result = if prune {
+                        builder.prune(&filtered_members)
+                    } else {
+                        builder.clean(&filtered_members, mode)
+                    };
+                    if let Err(e) = result {
+                        fail(format, "Clean failed", e);
                     }
                 }
                 Err(_e) => (),
             }
         }
 
-        Forge::Run { path, member, args, profile, release } => {
-            if let Err(e) = run_project(path, member, args, profile, release) {
-                eprintln!("Run failed: {}", e);
-                std::process::exit(1);
+        Command::Watch(opts) => {
+            if let Err(e) = watch_project(opts, verbose) {
+                fail(format, "Watch failed", e);
+            }
+        }
+
+        Command::Run(opts) => {
+            if let Err(e) = run_project(opts, verbose) {
+                fail(format, "Run failed", e);
+            }
+        }
+
+        Command::Test(opts) => {
+            if let Err(e) = run_tests(opts, verbose) {
+                fail(format, "Test failed", e);
+            }
+        }
+
+        Command::Install(opts) => {
+            if let Err(e) = install_project(opts, verbose) {
+                fail(format, "Install failed", e);
+            }
+        }
+
+        Command::Doc(opts) => {
+            if let Err(e) = document_project(opts) {
+                fail(format, "Doc failed", e);
+            }
+        }
+
+        Command::Generate(opts) => {
+            if let Err(e) = generate_build_file(opts) {
+                fail(format, "Generate failed", e);
             }
         }
 
-        Forge::Test { path, member, args, profile, release } => {
-            if let Err(e) = run_tests(path, member, args, profile, release) {
-                eprintln!("Test failed: {}", e);
-                std::process::exit(1);
+        Command::Tree(opts) => {
+            let path = opts.path.unwrap_or_else(|| std::env::current_dir().unwrap());
+            let printed = Workspace::new(&path)
+                .and_then(|workspace| tree::print_tree(&workspace, &opts.format));
+            if let Err(e) = printed {
+                fail(format, "Tree failed", e);
             }
         }
+
+        Command::Completions { shell } => {
+            Forge::clap().gen_completions_to("forge", shell, &mut std::io::stdout());
+        }
     }
-}
\ No newline at end of file
+}
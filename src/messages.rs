@@ -0,0 +1,170 @@
+use std::{
+    fmt::Display,
+    io::IsTerminal,
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+    time::Duration,
+};
+use serde_json::{Map, Value};
+use crate::error::ForgeError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    Human,
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(format!("Unknown message format: {}", s)),
+        }
+    }
+}
+
+/// Whether output is colored, as chosen with `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// On terminals, unless `NO_COLOR` is set to a non-empty value
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("Unknown color choice: {}", s)),
+        }
+    }
+}
+
+/// The process-wide `ColorChoice`, as its index.
+static COLOR_CHOICE: AtomicU8 = AtomicU8::new(ColorChoice::Auto as u8);
+
+/// Sets whether forge, and the compilers it runs, color their output.
+pub fn set_color_choice(choice: ColorChoice) {
+    COLOR_CHOICE.store(choice as u8, Ordering::Relaxed);
+}
+
+/// Whether output to `stream` is colored.
+pub fn colors(stream: &impl IsTerminal) -> bool {
+    match COLOR_CHOICE.load(Ordering::Relaxed) {
+        c if c == ColorChoice::Always as u8 => true,
+        c if c == ColorChoice::Never as u8 => false,
+        _ => stream.is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+    }
+}
+
+/// `text` in the SGR `style` (e.g. `1;31` for bold red) when `stream` is
+/// colored.
+fn paint(stream: &impl IsTerminal, style: &str, text: &str) -> String {
+    match colors(stream) {
+        true => format!("\x1b[{}m{}\x1b[0m", style, text),
+        false => text.to_string(),
+    }
+}
+
+/// Prints `Warning: <line>` to stderr.
+pub fn warning(line: impl Display) {
+    eprintln!("{}: {}", paint(&std::io::stderr(), "1;33", "Warning"), line);
+}
+
+/// Prints `<context>: <error>` to stderr, e.g. `Build failed: ...`.
+pub fn error(context: &str, error: impl Display) {
+    eprintln!("{}: {}", paint(&std::io::stderr(), "1;31", context), error);
+}
+
+/// Where the build reports what it does. With the JSON format every event
+/// is a single-line object on stdout, tagged with a `reason` like cargo's
+/// messages, and the lines meant for people move to stderr.
+#[derive(Debug, Clone, Copy)]
+pub struct Messages {
+    format: MessageFormat,
+    quiet: bool,
+}
+
+impl Messages {
+    pub fn new(format: MessageFormat) -> Self {
+        Messages { format, quiet: false }
+    }
+
+    /// Quiet messages drop status lines and the output of compilers and
+    /// linkers that succeed; errors are still reported.
+    pub fn quiet(self, quiet: bool) -> Self {
+        Messages { quiet, ..self }
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.format == MessageFormat::Json
+    }
+
+    /// A progress line such as `Compiling <file>`.
+    pub fn status(&self, line: impl Display) {
+        if self.quiet {
+            return;
+        }
+
+        match self.format {
+            MessageFormat::Human => println!("{}", line),
+            MessageFormat::Json => eprintln!("{}", line),
+        }
+    }
+
+    /// An error the build reports and carries on past, or finally fails
+    /// with: `<context>: <error>`, or an `error` object in the JSON format.
+    /// Both go to stderr.
+    pub fn error(&self, context: &str, error: &ForgeError) {
+        match self.format {
+            MessageFormat::Human => self::error(context, error),
+            MessageFormat::Json => eprintln!("{}", error.to_json(context)),
+        }
+    }
+
+    /// Whether compiler and linker output is shown while they run. When it
+    /// isn't, it ends up in the error of a failing command instead.
+    pub fn shows_tool_output(&self) -> bool {
+        !self.quiet && !self.is_json()
+    }
+
+    /// A line printed by a compiler or linker, e.g. a warning.
+    pub fn tool_output(&self, line: &str) {
+        if self.shows_tool_output() {
+            eprintln!("{}", line);
+        }
+    }
+
+    /// An event for tooling; `fields` must be a JSON object. Nothing is
+    /// printed in the human format.
+    pub fn event(&self, reason: &str, fields: Value) {
+        if !self.is_json() {
+            return;
+        }
+
+        let mut event = Map::new();
+        event.insert("reason".to_string(), Value::from(reason));
+        if let Value::Object(fields) = fields {
+            event.extend(fields);
+        }
+        println!("{}", Value::Object(event));
+    }
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Messages::new(MessageFormat::Human)
+    }
+}
+
+pub fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
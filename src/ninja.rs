@@ -0,0 +1,143 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Rules shared by every build edge; the edges carry their full command
+/// line in `$command`.
+const RULES: &str = "\
+rule cc
+  command = $command
+  description = Compiling $in
+  deps = gcc
+  depfile = $out.d
+
+rule cc_msvc
+  command = $command
+  description = Compiling $in
+  deps = msvc
+
+rule link
+  command = $command
+  description = Linking $out
+
+rule archive
+  command = $command
+  description = Archiving $out
+
+rule generate
+  command = $command
+  description = Generating $out
+  restat = 1
+";
+
+/// A `build.ninja` being put together.
+pub struct NinjaFile {
+    contents: String,
+}
+
+impl NinjaFile {
+    pub fn new() -> Self {
+        let mut contents = String::new();
+        contents.push_str("# Generated by forge generate ninja; changes are overwritten\n");
+        contents.push_str("ninja_required_version = 1.3\n\n");
+        contents.push_str(RULES);
+        NinjaFile { contents }
+    }
+
+    pub fn comment(&mut self, text: &str) {
+        let _ = write!(self.contents, "\n# {}\n", text);
+    }
+
+    /// An edge producing `outputs` from `inputs` by running `command`.
+    /// Changes to the `implicit` inputs rebuild the outputs as well, but
+    /// they don't show up in `$in`.
+    pub fn build(&mut self, outputs: &[PathBuf], rule: &str, inputs: &[PathBuf], implicit: &[PathBuf], command: &str) {
+        let _ = write!(self.contents, "build {}: {} {}", paths(outputs), rule, paths(inputs));
+        if !implicit.is_empty() {
+            let _ = write!(self.contents, " | {}", paths(implicit));
+        }
+        let _ = write!(self.contents, "\n  command = {}\n", command.replace('$', "$$"));
+    }
+
+    pub fn default(&mut self, targets: &[PathBuf]) {
+        let _ = write!(self.contents, "\ndefault {}\n", paths(targets));
+    }
+
+    pub fn finish(self) -> String {
+        self.contents
+    }
+}
+
+/// `cmd` as a line for the shell ninja runs commands with, setting the
+/// environment variables given to it.
+pub fn command_line(cmd: &Command) -> String {
+    let line = std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| quote(&arg.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let env: Vec<String> = cmd.get_envs()
+        .filter_map(|(key, value)| Some(format!("{}={}", key.to_string_lossy(), value?.to_string_lossy())))
+        .collect();
+    if env.is_empty() {
+        line
+    } else if cfg!(windows) {
+        let set: Vec<String> = env.iter().map(|var| format!("set \"{}\"", var)).collect();
+        format!("cmd /c \"{} && {}\"", set.join(" && "), line)
+    } else {
+        let env: Vec<String> = env.iter().map(|var| quote(var)).collect();
+        format!("env {} {}", env.join(" "), line)
+    }
+}
+
+/// A shell `command` run in `dir`, as `[[generate]]` commands are.
+pub fn shell_in(dir: &Path, command: &str) -> String {
+    let dir = quote(&dir.display().to_string());
+    if cfg!(windows) {
+        format!("cmd /c \"cd /d {} && {}\"", dir, command)
+    } else {
+        format!("cd {} && {}", dir, command)
+    }
+}
+
+/// `command` preceded by removing `output`, for tools like `ar` that add
+/// to an existing file instead of replacing it.
+pub fn replacing(output: &Path, command: String) -> String {
+    if cfg!(windows) {
+        format!("cmd /c \"del /q {} 2>nul & {}\"", quote(&output.display().to_string()), command)
+    } else {
+        format!("rm -f {} && {}", quote(&output.display().to_string()), command)
+    }
+}
+
+fn paths(paths: &[PathBuf]) -> String {
+    paths.iter().map(|path| escape_path(path)).collect::<Vec<_>>().join(" ")
+}
+
+fn escape_path(path: &Path) -> String {
+    path.display().to_string()
+        .replace('$', "$$")
+        .replace(' ', "$ ")
+        .replace(':', "$:")
+}
+
+/// Ninja passes commands to `/bin/sh -c` on Unix and straight to
+/// `CreateProcess` on Windows, which need different quoting.
+fn quote(arg: &str) -> String {
+    if cfg!(windows) {
+        if arg.is_empty() || arg.contains([' ', '\t', '"']) {
+            return format!("\"{}\"", arg.replace('"', "\\\""));
+        }
+        return arg.to_string();
+    }
+
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-+=/.,:@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
@@ -12,17 +12,69 @@ pub struct Target {
     pub env: Environment,
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Architecture {
     X86,
     X86_64,
     ARM,
     AArch64,
+    RISCV32,
     RISCV64,
+    Wasm32,
+    Wasm64,
+    PowerPC,
+    PowerPC64,
+    PowerPC64LE,
+    MIPS,
+    MIPSEL,
+    MIPS64,
+    MIPS64EL,
+    /// A versioned ARM or Thumb architecture such as `armv7`, `armv7a` or
+    /// `thumbv7em`, kept by its full name
+    ArmVariant(String),
     #[serde(other)]
     Unknown,
 }
 
+impl Architecture {
+    fn parse(name: &str) -> Option<Self> {
+        let arch = match name {
+            "x86_64" | "amd64" => Architecture::X86_64,
+            "i686" | "i586" | "i386" | "x86" => Architecture::X86,
+            "aarch64" | "arm64" => Architecture::AArch64,
+            "arm" => Architecture::ARM,
+            "wasm32" => Architecture::Wasm32,
+            "wasm64" => Architecture::Wasm64,
+            "powerpc" => Architecture::PowerPC,
+            "powerpc64" => Architecture::PowerPC64,
+            "powerpc64le" => Architecture::PowerPC64LE,
+            "mips" => Architecture::MIPS,
+            "mipsel" => Architecture::MIPSEL,
+            "mips64" => Architecture::MIPS64,
+            "mips64el" => Architecture::MIPS64EL,
+            // RISC-V names may carry ISA extensions (riscv32imac), which
+            // toolchain prefixes leave out
+            _ if name.starts_with("riscv32") => Architecture::RISCV32,
+            _ if name.starts_with("riscv64") => Architecture::RISCV64,
+            _ if name.starts_with("armv") || name.starts_with("thumbv") => {
+                Architecture::ArmVariant(name.to_string())
+            }
+            _ => return None,
+        };
+        Some(arch)
+    }
+
+    /// The name GNU toolchains use in their prefix. ARM toolchains are
+    /// named `arm-...` whatever variant they compile for.
+    pub fn toolchain_name(&self) -> String {
+        match self {
+            Architecture::ArmVariant(_) => Architecture::ARM.to_string(),
+            arch => arch.to_string(),
+        }
+    }
+}
+
 impl fmt::Display for Architecture {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -30,7 +82,18 @@ impl fmt::Display for Architecture {
             Architecture::X86 => write!(f, "i686"),
             Architecture::ARM => write!(f, "arm"),
             Architecture::AArch64 => write!(f, "aarch64"),
+            Architecture::RISCV32 => write!(f, "riscv32"),
             Architecture::RISCV64 => write!(f, "riscv64"),
+            Architecture::Wasm32 => write!(f, "wasm32"),
+            Architecture::Wasm64 => write!(f, "wasm64"),
+            Architecture::PowerPC => write!(f, "powerpc"),
+            Architecture::PowerPC64 => write!(f, "powerpc64"),
+            Architecture::PowerPC64LE => write!(f, "powerpc64le"),
+            Architecture::MIPS => write!(f, "mips"),
+            Architecture::MIPSEL => write!(f, "mipsel"),
+            Architecture::MIPS64 => write!(f, "mips64"),
+            Architecture::MIPS64EL => write!(f, "mips64el"),
+            Architecture::ArmVariant(name) => write!(f, "{}", name),
             Architecture::Unknown => write!(f, "unknown"),
         }
     }
@@ -78,11 +141,17 @@ impl fmt::Display for OS {
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Environment {
     GNU,
+    GNUEABI,
+    GNUEABIHF,
     MSVC,
     Musl,
+    EABI,
+    EABIHF,
+    ELF,
     None,
     #[serde(other)]
     Unknown,
@@ -93,43 +162,40 @@ impl FromStr for Target {
 
     fn from_str(s: &str) -> ForgeResult<Self> {
         let parts: Vec<&str> = s.split('-').collect();
-        if parts.len() < 3 {
+        if parts.len() < 2 || parts.iter().any(|part| part.is_empty()) {
             return Err(ForgeError::InvalidTarget("Invalid target triple".to_string()));
         }
 
-        let arch = match parts[0] {
-            "x86_64" => Architecture::X86_64,
-            "i686" => Architecture::X86,
-            "aarch64" => Architecture::AArch64,
-            "arm" => Architecture::ARM,
-            "riscv64" => Architecture::RISCV64,
-            _ => return Err(ForgeError::InvalidTarget(format!("Unknown architecture: {}", parts[0]))),
-        };
+        let arch = Architecture::parse(parts[0])
+            .ok_or_else(|| ForgeError::InvalidTarget(format!("Unknown architecture: {}", parts[0])))?;
 
-        let vendor = match parts[1] {
-            "pc" => Vendor::PC,
-            "unknown" => Vendor::Unknown,
-            "apple" => Vendor::Apple,
-            _ => Vendor::Other,
+        // The vendor is optional: `arm-none-eabi` and `aarch64-linux-gnu`
+        // name the OS right after the architecture
+        let (vendor, rest) = if parts.len() == 2 || parse_os(parts[1]).is_some() {
+            (Vendor::Unknown, &parts[1..])
+        } else {
+            let vendor = match parts[1] {
+                "pc" => Vendor::PC,
+                "unknown" => Vendor::Unknown,
+                "apple" => Vendor::Apple,
+                _ => Vendor::Other,
+            };
+            (vendor, &parts[2..])
         };
 
-        let os = match parts[2] {
-            "linux" => OS::Linux,
-            "windows" => OS::Windows,
-            "darwin" => OS::Darwin,
-            "none" => OS::None,
-            _ => OS::Unknown,
-        };
+        let os = parse_os(rest[0]).unwrap_or(OS::Unknown);
 
-        let env = if parts.len() > 3 {
-            match parts[3] {
-                "gnu" => Environment::GNU,
-                "msvc" => Environment::MSVC,
-                "musl" => Environment::Musl,
-                _ => Environment::Unknown,
-            }
-        } else {
-            Environment::None
+        let env = match rest.get(1) {
+            Some(&"gnu") => Environment::GNU,
+            Some(&"gnueabi") => Environment::GNUEABI,
+            Some(&"gnueabihf") => Environment::GNUEABIHF,
+            Some(&"msvc") => Environment::MSVC,
+            Some(&"musl") => Environment::Musl,
+            Some(&"eabi") => Environment::EABI,
+            Some(&"eabihf") => Environment::EABIHF,
+            Some(&"elf") => Environment::ELF,
+            Some(_) => Environment::Unknown,
+            None => Environment::None,
         };
 
         Ok(Target {
@@ -141,17 +207,18 @@ impl FromStr for Target {
     }
 }
 
+fn parse_os(name: &str) -> Option<OS> {
+    match name {
+        "linux" => Some(OS::Linux),
+        "windows" => Some(OS::Windows),
+        "darwin" => Some(OS::Darwin),
+        "none" => Some(OS::None),
+        _ => None,
+    }
+}
+
 impl Display for Target {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let arch = match self.arch {
-            Architecture::X86_64 => "x86_64",
-            Architecture::X86 => "i686",
-            Architecture::AArch64 => "aarch64",
-            Architecture::ARM => "arm",
-            Architecture::RISCV64 => "riscv64",
-            Architecture::Unknown => "unknown",
-        };
-
         let vendor = match self.vendor {
             Vendor::PC => "pc",
             Vendor::Unknown => "unknown",
@@ -169,34 +236,122 @@ impl Display for Target {
 
         let env = match self.env {
             Environment::GNU => "-gnu",
+            Environment::GNUEABI => "-gnueabi",
+            Environment::GNUEABIHF => "-gnueabihf",
             Environment::MSVC => "-msvc",
             Environment::Musl => "-musl",
+            Environment::EABI => "-eabi",
+            Environment::EABIHF => "-eabihf",
+            Environment::ELF => "-elf",
             Environment::None => "",
             Environment::Unknown => "-unknown",
         };
 
-        write!(f, "{}", format!("{}-{}-{}{}", arch, vendor, os, env))
+        write!(f, "{}-{}-{}{}", self.arch, vendor, os, env)
     }
 }
 
 impl Target {
+    /// The machine forge runs on, named the way toolchains name it:
+    /// `std::env::consts::OS` says `macos` where triples say `apple-darwin`.
     pub fn host() -> ForgeResult<Self> {
-        let triple = format!("{}-unknown-{}",
-                             std::env::consts::ARCH,
-                             std::env::consts::OS
-        );
-        Self::from_str(&triple)
+        let (vendor, os) = match std::env::consts::OS {
+            "macos" => ("apple", "darwin"),
+            "windows" => ("pc", "windows"),
+            os => ("unknown", os),
+        };
+
+        let env = if cfg!(target_env = "msvc") {
+            "-msvc"
+        } else if cfg!(target_env = "gnu") {
+            "-gnu"
+        } else if cfg!(target_env = "musl") {
+            "-musl"
+        } else {
+            ""
+        };
+
+        Self::from_str(&format!("{}-{}-{}{}", std::env::consts::ARCH, vendor, os, env))
+    }
+
+    /// Whether binaries built for this target can run natively on `host`.
+    pub fn runs_on(&self, host: &Target) -> bool {
+        self.arch == host.arch && self.os == host.os
     }
 
     pub fn is_windows(&self) -> bool {
         matches!(self.os, OS::Windows)
     }
 
+    #[allow(dead_code)]
     pub fn is_unix(&self) -> bool {
         matches!(self.os, OS::Linux | OS::Darwin)
     }
 
+    pub fn static_library_name(&self, name: &str) -> String {
+        if self.is_windows() && self.env != Environment::GNU {
+            format!("{}.lib", name)
+        } else {
+            format!("lib{}.a", name)
+        }
+    }
+
+    pub fn shared_library_name(&self, name: &str) -> String {
+        match self.os {
+            OS::Windows => format!("{}.dll", name),
+            OS::Darwin => format!("lib{}.dylib", name),
+            _ => format!("lib{}.so", name),
+        }
+    }
+
     pub fn executable_extension(&self) -> &'static str {
         if self.is_windows() { ".exe" } else { "" }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_has_known_os() {
+        let host = Target::host().unwrap();
+        assert_ne!(host.os, OS::Unknown);
+        if cfg!(target_os = "macos") {
+            assert_eq!(host.vendor, Vendor::Apple);
+            assert_eq!(host.os, OS::Darwin);
+        }
+    }
+
+    #[test]
+    fn parses_bare_metal_arm() {
+        let target = Target::from_str("arm-none-eabi").unwrap();
+        assert_eq!(target.arch, Architecture::ARM);
+        assert_eq!(target.vendor, Vendor::Unknown);
+        assert_eq!(target.os, OS::None);
+        assert_eq!(target.env, Environment::EABI);
+    }
+
+    #[test]
+    fn parses_triple_without_vendor() {
+        let target = Target::from_str("aarch64-linux-gnu").unwrap();
+        assert_eq!(target.arch, Architecture::AArch64);
+        assert_eq!(target.vendor, Vendor::Unknown);
+        assert_eq!(target.os, OS::Linux);
+        assert_eq!(target.env, Environment::GNU);
+    }
+
+    #[test]
+    fn parses_riscv_with_extensions() {
+        let target = Target::from_str("riscv32imac-unknown-none-elf").unwrap();
+        assert_eq!(target.arch, Architecture::RISCV32);
+        assert_eq!(target.os, OS::None);
+        assert_eq!(target.env, Environment::ELF);
+        assert_eq!(target.arch.toolchain_name(), "riscv32");
+    }
+
+    #[test]
+    fn rejects_empty_parts() {
+        assert!(Target::from_str("x86_64").is_err());
+        assert!(Target::from_str("x86_64--linux").is_err());
+    }
+}
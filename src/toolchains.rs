@@ -1,7 +1,8 @@
 use crate::{
     error::{ForgeError, ForgeResult},
-    target::Target,
+    target::{Environment, Target, OS},
 };
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
@@ -11,6 +12,7 @@ pub struct Toolchain {
     target: Target,
     sysroot: Option<PathBuf>,
     extra_flags: Vec<String>,
+    env: BTreeMap<String, String>,
 }
 
 impl Toolchain {
@@ -31,51 +33,173 @@ impl Toolchain {
             target,
             sysroot: sysroot.map(PathBuf::from),
             extra_flags,
+            env: BTreeMap::new(),
         })
     }
 
+    /// Sets environment variables for the compiler and tools, e.g. a `PATH`
+    /// reaching an SDK's helper binaries.
+    pub fn with_env(mut self, env: BTreeMap<String, String>) -> Self {
+        self.env = env;
+        self
+    }
+
     pub fn get_compiler_command(&self, compiler: &str) -> Command {
         let compiler_path = self.get_compiler_path(compiler);
         let mut cmd = Command::new(&compiler_path);
+        cmd.envs(&self.env);
+
+        // Prefixed GNU cross compilers know their target already and reject
+        // --target; only a bare driver (clang) needs to be told
+        let arch_names = [self.target.arch.to_string(), self.target.arch.toolchain_name()];
+        let prefixed = compiler_path.file_name().is_some_and(|name| {
+            let name = name.to_string_lossy();
+            arch_names.iter().any(|arch| name.starts_with(&format!("{}-", arch)))
+        });
+        if !prefixed {
+            cmd.arg(format!("--target={}", self.target));
+        }
 
-        // Add target specification
-        cmd.arg(format!("--target={}", self.target.to_string()));
+        cmd.args(self.flags());
+        cmd
+    }
 
-        // Add sysroot if specified
+    /// The sysroot and extra flags passed to every compiler invocation.
+    pub fn flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
         if let Some(sysroot) = &self.sysroot {
-            cmd.arg(format!("--sysroot={}", sysroot.display()));
+            flags.push(format!("--sysroot={}", sysroot.display()));
         }
+        flags.extend(self.extra_flags.iter().cloned());
+        flags
+    }
 
-        // Add any extra flags
-        cmd.args(&self.extra_flags);
-
+    /// A command running `tool` from the toolchain, with its environment.
+    pub fn get_tool_command(&self, tool: &str) -> Command {
+        let mut cmd = Command::new(self.get_tool_path(tool));
+        cmd.envs(&self.env);
         cmd
     }
 
     pub fn get_compiler_path(&self, compiler: &str) -> PathBuf {
-        if self.target.is_windows() {
-            self.root.join(format!("{}.exe", compiler))
-        } else {
-            let prefix = format!(
-                "{}-{}-{}-",
-                self.target.arch.to_string().to_lowercase(),
-                self.target.vendor.to_string().to_lowercase(),
-                self.target.os.to_string().to_lowercase()
-            );
-            self.root.join(format!("{}{}", prefix, compiler))
+        self.resolve_compiler(compiler)
+            .unwrap_or_else(|_| self.root.join(&self.tool_candidates(compiler, false)[0]))
+    }
+
+    /// Path of a tool (`g++`, `objcopy`, `nm`, ...) for the target. Falls
+    /// back to the conventional name under the toolchain root when no
+    /// candidate exists, so that running it reports the missing binary.
+    pub fn get_tool_path(&self, tool: &str) -> PathBuf {
+        self.resolve_tool(tool, true)
+            .unwrap_or_else(|_| self.root.join(&self.tool_candidates(tool, true)[0]))
+    }
+
+    /// The concrete compiler binary for the target, or an error listing
+    /// every name and directory that was tried. Only clang is accepted
+    /// unprefixed, as the host's other compilers can't be retargeted.
+    pub fn resolve_compiler(&self, compiler: &str) -> ForgeResult<PathBuf> {
+        self.resolve_tool(compiler, compiler.contains("clang"))
+    }
+
+    fn resolve_tool(&self, tool: &str, allow_bare: bool) -> ForgeResult<PathBuf> {
+        // An explicit path is used as given
+        if Path::new(tool).components().count() > 1 {
+            return match Path::new(tool).is_file() {
+                true => Ok(PathBuf::from(tool)),
+                false => Err(ForgeError::Config(format!("Compiler not found: {}", tool))),
+            };
+        }
+
+        let candidates = self.tool_candidates(tool, allow_bare);
+        let dirs = self.search_dirs();
+
+        for candidate in &candidates {
+            for dir in &dirs {
+                let path = dir.join(candidate);
+                if path.is_file() {
+                    return Ok(path);
+                }
+            }
         }
+
+        Err(ForgeError::Config(format!(
+            "No {} found for {} (tried {} in {} and PATH)",
+            tool,
+            self.target,
+            candidates.join(", "),
+            self.root.display()
+        )))
+    }
+
+    /// Binary names a toolchain may use for `tool`, most specific first:
+    /// the full triple, the triple without its environment, the triple
+    /// without a vendor (`aarch64-linux-gnu-`), and optionally the bare name.
+    /// ARM variants are then tried under plain `arm` (`arm-none-eabi-`).
+    /// Windows GNU targets try MinGW's `x86_64-w64-mingw32-` first.
+    fn tool_candidates(&self, tool: &str, allow_bare: bool) -> Vec<String> {
+        let target = &self.target;
+        let base = format!("{}-{}-{}", target.arch, target.vendor, target.os);
+        let triple = target.to_string();
+        let env = triple.strip_prefix(&base).unwrap_or_default();
+
+        let mut prefixes = Vec::new();
+        for arch in [target.arch.to_string(), target.arch.toolchain_name()] {
+            prefixes.extend([
+                format!("{}-{}-{}{}", arch, target.vendor, target.os, env),
+                format!("{}-{}-{}", arch, target.vendor, target.os),
+                format!("{}-{}{}", arch, target.os, env),
+                format!("{}-{}", arch, target.os),
+            ]);
+        }
+        // MinGW-w64 names its tools <arch>-w64-mingw32-, whatever the
+        // triple says
+        if target.os == OS::Windows && target.env == Environment::GNU {
+            prefixes.insert(0, format!("{}-w64-mingw32", target.arch));
+        }
+        // Bare-metal GCC for ARM is arm-none-eabi, hard-float or not
+        if env == "-eabihf" {
+            prefixes.push(format!("{}-{}-eabi", target.arch.toolchain_name(), target.os));
+        }
+
+        let mut candidates: Vec<String> = Vec::new();
+        for prefix in prefixes {
+            let name = host_executable(&format!("{}-{}", prefix, tool));
+            if !candidates.contains(&name) {
+                candidates.push(name);
+            }
+        }
+        if allow_bare {
+            candidates.push(host_executable(tool));
+        }
+        candidates
+    }
+
+    fn search_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = vec![self.root.clone(), self.root.join("bin")];
+        if let Some(path) = std::env::var_os("PATH") {
+            dirs.extend(std::env::split_paths(&path));
+        }
+        dirs
+    }
+
+    pub fn target(&self) -> &Target {
+        &self.target
     }
 
+    #[allow(dead_code)]
     pub fn get_sysroot(&self) -> Option<&Path> {
         self.sysroot.as_deref()
     }
 
+    #[allow(dead_code)]
     pub fn with_extra_flags(mut self, flags: Vec<String>) -> Self {
         self.extra_flags = flags;
         self
     }
 
-    pub fn verify(&self) -> ForgeResult<()> {
+    /// Checks that the toolchain's directories exist and that `compiler`
+    /// resolves to a binary for the target.
+    pub fn verify(&self, compiler: &str) -> ForgeResult<()> {
         if !self.root.exists() {
             return Err(ForgeError::Config(format!(
                 "Toolchain root directory does not exist: {}",
@@ -92,6 +216,16 @@ impl Toolchain {
             }
         }
 
+        self.resolve_compiler(compiler)?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// `name` as an executable file name on the host running forge.
+fn host_executable(name: &str) -> String {
+    if cfg!(windows) {
+        format!("{}.exe", name)
+    } else {
+        name.to_string()
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::{HashMap, HashSet};
+use forge::{
+    workspace::Workspace,
+    error::ForgeResult,
+};
+
+/// Prints every member with its dependencies below it, numbered in build
+/// order, as an indented tree or as Graphviz `dot`. Dependency cycles are
+/// marked instead of followed, and reported as an error once printed.
+pub fn print_tree(workspace: &Workspace, format: &str) -> ForgeResult<()> {
+    let graph = workspace.dependency_graph();
+    let order = workspace.get_build_order();
+    let position: HashMap<&str, usize> = order.iter()
+        .flatten()
+        .enumerate()
+        .map(|(index, member)| (member.name.as_str(), index + 1))
+        .collect();
+
+    let names: Vec<&str> = workspace.members.iter().map(|m| m.name.as_str()).collect();
+    if format == "dot" {
+        print_dot(&names, &graph, &position);
+        return order.map(|_| ());
+    }
+
+    let mut tree = Tree { graph: &graph, position: &position, path: Vec::new(), expanded: HashSet::new() };
+    let dependents: HashSet<&str> = graph.values().flatten().map(String::as_str).collect();
+    for name in names.iter().filter(|name| !dependents.contains(*name)) {
+        tree.print(name, "", None);
+    }
+    // Members within a cycle may have no root above them
+    for name in &names {
+        if !tree.expanded.contains(name) {
+            tree.print(name, "", None);
+        }
+    }
+
+    let order: Vec<&str> = order?.iter().map(|m| m.name.as_str()).collect();
+    println!("\nBuild order: {}", order.join(", "));
+    Ok(())
+}
+
+struct Tree<'a> {
+    graph: &'a HashMap<String, Vec<String>>,
+    position: &'a HashMap<&'a str, usize>,
+    /// Members from the root down to the one being printed
+    path: Vec<&'a str>,
+    expanded: HashSet<&'a str>,
+}
+
+impl<'a> Tree<'a> {
+    /// Prints `name` and, unless already shown, its dependencies. `last`
+    /// tells whether it is its parent's last dependency; `None` for roots.
+    fn print(&mut self, name: &'a str, indent: &str, last: Option<bool>) {
+        let connector = match last {
+            None => "",
+            Some(true) => "└── ",
+            Some(false) => "├── ",
+        };
+        let label = label(name, self.position);
+        let deps = self.graph.get(name).map(Vec::as_slice).unwrap_or_default();
+
+        if self.path.contains(&name) {
+            println!("{}{}{} (cycle)", indent, connector, label);
+            return;
+        }
+        if !self.expanded.insert(name) && !deps.is_empty() {
+            println!("{}{}{} (*)", indent, connector, label);
+            return;
+        }
+        println!("{}{}{}", indent, connector, label);
+
+        let indent = match last {
+            None => String::new(),
+            Some(true) => format!("{}    ", indent),
+            Some(false) => format!("{}│   ", indent),
+        };
+        self.path.push(name);
+        for (index, dep) in deps.iter().enumerate() {
+            self.print(dep, &indent, Some(index + 1 == deps.len()));
+        }
+        self.path.pop();
+    }
+}
+
+/// Prints the graph in Graphviz `dot`, with the edges of cycles in red.
+fn print_dot(names: &[&str], graph: &HashMap<String, Vec<String>>, position: &HashMap<&str, usize>) {
+    println!("digraph workspace {{");
+    for name in names {
+        println!("    {:?} [label={:?}];", name, label(name, position));
+    }
+    for name in names {
+        for dep in graph.get(*name).into_iter().flatten() {
+            if reaches(graph, dep, name) {
+                println!("    {:?} -> {:?} [color=red];", name, dep);
+            } else {
+                println!("    {:?} -> {:?};", name, dep);
+            }
+        }
+    }
+    println!("}}");
+}
+
+fn label(name: &str, position: &HashMap<&str, usize>) -> String {
+    match position.get(name) {
+        Some(position) => format!("{} [{}]", name, position),
+        None => name.to_string(),
+    }
+}
+
+/// Whether `to` is among the dependencies of `from`, direct or not, or is
+/// `from` itself.
+fn reaches(graph: &HashMap<String, Vec<String>>, from: &str, to: &str) -> bool {
+    let mut stack = vec![from];
+    let mut seen = HashSet::new();
+    while let Some(name) = stack.pop() {
+        if name == to {
+            return true;
+        }
+        if seen.insert(name) {
+            stack.extend(graph.get(name).into_iter().flatten().map(String::as_str));
+        }
+    }
+    false
+}
@@ -0,0 +1,144 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+use log::debug;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use forge::{
+    builder::Builder,
+    workspace::{Workspace, WorkspaceMember},
+    error::{ForgeError, ForgeResult},
+    messages,
+};
+
+/// Editors often write a file several times per save; changes arriving
+/// within this window are handled as one rebuild.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Builds `members` once, then rebuilds whenever files in their source or
+/// include directories change. Runs until the process is interrupted.
+pub fn watch(builder: &Builder, workspace: &Workspace, members: &[&WorkspaceMember]) -> ForgeResult<()> {
+    let members: Vec<&WorkspaceMember> = if members.is_empty() {
+        workspace.members.iter().collect()
+    } else {
+        members.to_vec()
+    };
+
+    rebuild(builder, &members);
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let _ = tx.send(event);
+    }).map_err(|e| ForgeError::Build(format!("Failed to start file watcher: {}", e)))?;
+
+    let mut watched: Vec<PathBuf> = Vec::new();
+    for member in workspace.members.iter() {
+        for dir in member.get_source_dirs().into_iter().chain(member.get_include_dirs()) {
+            // Events report paths below the watched one, so watch canonical
+            // paths to compare them against member directories
+            let Ok(dir) = dir.canonicalize() else {
+                continue;
+            };
+            if watched.contains(&dir) {
+                continue;
+            }
+            watcher.watch(&dir, RecursiveMode::Recursive)
+                .map_err(|e| ForgeError::Build(format!("Failed to watch {}: {}", dir.display(), e)))?;
+            watched.push(dir);
+        }
+    }
+
+    println!("Watching {} directories for changes", watched.len());
+
+    loop {
+        let Ok(event) = rx.recv() else {
+            return Ok(());
+        };
+
+        let mut changed = changed_paths(event);
+        loop {
+            match rx.recv_timeout(DEBOUNCE) {
+                Ok(event) => changed.extend(changed_paths(event)),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+
+        let affected = affected_members(workspace, &members, &changed)?;
+        if affected.is_empty() {
+            continue;
+        }
+
+        debug!("Changed: {:?}", changed);
+        rebuild(builder, &affected);
+    }
+}
+
+fn rebuild(builder: &Builder, members: &[&WorkspaceMember]) {
+    let start = Instant::now();
+    match builder.build(members) {
+        Ok(_) => println!("[rebuilt in {:.1}s]", start.elapsed().as_secs_f32()),
+        Err(e) => {
+            messages::error("Build failed", e);
+            println!("[failed in {:.1}s]", start.elapsed().as_secs_f32());
+        }
+    }
+}
+
+fn changed_paths(event: notify::Result<Event>) -> Vec<PathBuf> {
+    match event {
+        Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) => {
+            event.paths
+        }
+        Ok(_) => Vec::new(),
+        Err(e) => {
+            messages::warning(format_args!("file watcher error: {}", e));
+            Vec::new()
+        }
+    }
+}
+
+/// The watched members owning one of the changed files, plus the watched
+/// members depending on those.
+fn affected_members<'a>(
+    workspace: &Workspace,
+    members: &[&'a WorkspaceMember],
+    changed: &[PathBuf],
+) -> ForgeResult<Vec<&'a WorkspaceMember>> {
+    let changed: Vec<&PathBuf> = changed.iter()
+        .filter(|path| !is_build_output(workspace, path))
+        .collect();
+
+    let owners: Vec<&WorkspaceMember> = workspace.members.iter()
+        .filter(|member| {
+            let dirs: Vec<PathBuf> = member.get_source_dirs().into_iter()
+                .chain(member.get_include_dirs())
+                .filter_map(|dir| dir.canonicalize().ok())
+                .collect();
+            changed.iter().any(|path| dirs.iter().any(|dir| path.starts_with(dir)))
+        })
+        .collect();
+
+    let mut affected = Vec::new();
+    for member in members {
+        let depends_on_owner = workspace.dependencies_of(member)?
+            .iter()
+            .any(|dep| owners.iter().any(|owner| owner.name == dep.name));
+
+        if depends_on_owner || owners.iter().any(|owner| owner.name == member.name) {
+            affected.push(*member);
+        }
+    }
+
+    Ok(affected)
+}
+
+/// Source directories may contain the build directory (e.g. `src = "."`),
+/// whose churn must not trigger rebuilds of its own.
+fn is_build_output(workspace: &Workspace, path: &Path) -> bool {
+    workspace.members.iter()
+        .filter_map(|member| member.get_build_dir().canonicalize().ok())
+        .any(|dir| path.starts_with(dir))
+        || path.components().any(|c| c.as_os_str() == ".forge_cache")
+}
@@ -1,18 +1,26 @@
 use crate::{
-    config::Config,
+    config::{process_env, Config, OutputType},
     error::{ForgeError, ForgeResult},
+    target::{Architecture, Environment, Target, Vendor, OS},
 };
+use globset::{GlobBuilder, GlobMatcher};
 use std::{
     collections::{HashMap, HashSet},
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
+    str::FromStr,
 };
 
+/// Token that may prefix a member path (`src`, `src_roots`, `include`) to
+/// resolve it against the workspace root instead of the member directory.
+pub const WORKSPACE_TOKEN: &str = "$WORKSPACE";
+
 #[derive(Debug, Clone)]
 pub struct Workspace {
     pub root_path: PathBuf,
     pub root_config: Config,
     pub members: Vec<WorkspaceMember>,
     pub selected_profile: Option<String>,
+    pub build_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone)]
@@ -21,7 +29,14 @@ pub struct WorkspaceMember {
     pub path: PathBuf,
     pub config: Config,
     pub selected_profile: Option<String>,
+    /// Target given on the command line, ahead of `[cross]`
+    pub selected_target: Option<String>,
+    /// The workspace's `default_target`, behind `[cross]`
+    pub default_target: Option<String>,
     pub workspace_root: PathBuf,
+    /// The workspace's build directory, for members without their own
+    pub workspace_build_dir: PathBuf,
+    pub build_dir: Option<PathBuf>,
 }
 
 impl Workspace {
@@ -30,43 +45,91 @@ impl Workspace {
         let mut members = Vec::new();
 
         if !root_config.build.target.is_empty() {
-            members.push(WorkspaceMember {
-                name: "root".to_string(),
+            let member = WorkspaceMember {
+                name: root_config.package_name().unwrap_or("root").to_string(),
                 path: root_path.to_path_buf(),
                 config: root_config.clone(),
                 selected_profile: None,
-                workspace_root: root_path.to_path_buf()
-            });
+                selected_target: None,
+                default_target: root_config.build.default_target.clone(),
+                workspace_root: root_path.to_path_buf(),
+                workspace_build_dir: root_path.join(root_config.paths.build_path()),
+                build_dir: None,
+            };
+            member.validate_paths()?;
+            members.push(member);
         }
 
-        for member_name in &root_config.workspace.members {
-            if root_config.workspace.exclude.contains(member_name) {
-                continue;
-            }
-
+        for member_name in &member_dirs(root_path, &root_config)? {
             let member_path = root_path.join(member_name);
             let config_path = member_path.join("forge.toml");
             let config = if config_path.exists() {
                 Config::load(&config_path)?
             } else {
-                Config::default_for_member(member_name)
+                let mut config = Config::default_for_member(member_name);
+                config.build.apply_compiler_env(&process_env);
+                config
             };
 
-            members.push(WorkspaceMember {
-                name: member_name.clone(),
+            let member = WorkspaceMember {
+                name: config.package_name().unwrap_or(member_name).to_string(),
                 path: member_path,
                 config,
                 selected_profile: None,
-                workspace_root: root_path.to_path_buf()
-            });
+                selected_target: None,
+                default_target: root_config.build.default_target.clone(),
+                workspace_root: root_path.to_path_buf(),
+                workspace_build_dir: root_path.join(root_config.paths.build_path()),
+                build_dir: None,
+            };
+            member.validate_paths()?;
+            members.push(member);
         }
 
-        Ok(Workspace {
+        if let Some(missing) = root_config.workspace.default_members.iter()
+            .find(|name| !members.iter().any(|m| &m.name == *name))
+        {
+            return Err(ForgeError::Workspace(format!(
+                "Default member {} is not a workspace member",
+                missing
+            )));
+        }
+
+        let workspace = Workspace {
             root_path: root_path.to_path_buf(),
             root_config,
             members,
             selected_profile: None,
-        })
+            build_dir: None,
+        };
+        workspace.validate_dependencies()?;
+        Ok(workspace)
+    }
+
+    /// Checks every `dependencies` entry up front, listing all unknown
+    /// and self dependencies at once rather than the first one a build
+    /// happens to reach.
+    fn validate_dependencies(&self) -> ForgeResult<()> {
+        let mut problems = Vec::new();
+        let known = |name: &String| self.members.iter().any(|m| &m.name == name);
+        for (member, deps) in &self.root_config.workspace.dependencies {
+            if !known(member) {
+                problems.push(format!("dependencies declared for unknown member {}", member));
+            }
+            for dep in deps {
+                if dep == member {
+                    problems.push(format!("{} depends on itself", member));
+                } else if !known(dep) {
+                    problems.push(format!("{} depends on unknown member {}", member, dep));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        problems.sort();
+        Err(ForgeError::Workspace(format!("Invalid dependencies: {}", problems.join(", "))))
     }
 
     pub fn set_profile(&mut self, profile: Option<String>) {
@@ -76,6 +139,35 @@ impl Workspace {
         }
     }
 
+    /// Builds every member for `target` rather than its `[cross]` target or
+    /// the `default_target`.
+    pub fn set_target(&mut self, target: Option<String>) {
+        for member in &mut self.members {
+            member.selected_target = target.clone();
+        }
+    }
+
+    /// Redirects every member's build output below `dir` instead of the
+    /// configured build path inside the workspace.
+    pub fn set_build_dir(&mut self, dir: Option<PathBuf>) {
+        self.build_dir = dir.clone();
+        for member in &mut self.members {
+            member.build_dir = dir.clone();
+        }
+    }
+
+    /// The directory holding every member's build directory.
+    pub fn build_root(&self) -> PathBuf {
+        match &self.build_dir {
+            Some(dir) => dir.clone(),
+            None => self.root_path.join(self.root_config.paths.build_path()),
+        }
+    }
+
+    pub fn root_member(&self) -> Option<&WorkspaceMember> {
+        self.members.iter().find(|m| m.path == self.root_path)
+    }
+
     pub fn filter_members(&self, filter: &[String]) -> Vec<&WorkspaceMember> {
         if filter.is_empty() {
             self.members.iter().collect()
@@ -87,21 +179,43 @@ impl Workspace {
         }
     }
 
-    pub fn get_build_order(&self) -> ForgeResult<Vec<&WorkspaceMember>> {
-        let mut visited = HashSet::new();
-        let mut order = Vec::new();
-        let mut temp_visited = HashSet::new();
+    /// The members named in `filter`, or the `default_members` when no
+    /// filter is given. Without either, every member.
+    pub fn selected_members(&self, filter: &[String]) -> Vec<&WorkspaceMember> {
+        let defaults = &self.root_config.workspace.default_members;
+        if filter.is_empty() && !defaults.is_empty() {
+            self.filter_members(defaults)
+        } else {
+            self.filter_members(filter)
+        }
+    }
 
-        let mut graph: HashMap<String, Vec<String>> = HashMap::new();
-        for member in &self.members {
-            graph.insert(
+    /// The only default member, if exactly one is configured.
+    pub fn default_member(&self) -> Option<&WorkspaceMember> {
+        match self.root_config.workspace.default_members.as_slice() {
+            [name] => self.members.iter().find(|m| &m.name == name),
+            _ => None,
+        }
+    }
+
+    /// Each member's direct dependencies, by name.
+    pub fn dependency_graph(&self) -> HashMap<String, Vec<String>> {
+        self.members.iter()
+            .map(|member| (
                 member.name.clone(),
                 self.root_config.workspace.dependencies
                     .get(&member.name)
                     .cloned()
                     .unwrap_or_default(),
-            );
-        }
+            ))
+            .collect()
+    }
+
+    pub fn get_build_order(&self) -> ForgeResult<Vec<&WorkspaceMember>> {
+        let mut visited = HashSet::new();
+        let mut order = Vec::new();
+        let mut path = Vec::new();
+        let graph = self.dependency_graph();
 
         for member in &self.members {
             if !visited.contains(&member.name) {
@@ -109,7 +223,7 @@ impl Workspace {
                     member,
                     &graph,
                     &mut visited,
-                    &mut temp_visited,
+                    &mut path,
                     &mut order,
                 )?;
             }
@@ -118,18 +232,93 @@ impl Workspace {
         Ok(order)
     }
 
+    /// Build order grouped into levels: every member only depends on
+    /// members of earlier levels, so members within a level can be built
+    /// concurrently.
+    pub fn get_build_levels(&self) -> ForgeResult<Vec<Vec<&WorkspaceMember>>> {
+        let mut levels: Vec<Vec<&WorkspaceMember>> = Vec::new();
+        let mut member_levels: HashMap<&str, usize> = HashMap::new();
+
+        for member in self.get_build_order()? {
+            let level = self.root_config.workspace.dependencies
+                .get(&member.name)
+                .into_iter()
+                .flatten()
+                .filter_map(|dep| member_levels.get(dep.as_str()))
+                .map(|level| level + 1)
+                .max()
+                .unwrap_or(0);
+
+            member_levels.insert(&member.name, level);
+            if levels.len() <= level {
+                levels.resize_with(level + 1, Vec::new);
+            }
+            levels[level].push(member);
+        }
+
+        Ok(levels)
+    }
+
+    /// Transitive workspace dependencies of `member`, ordered so that every
+    /// member comes before the members it depends on (the order static
+    /// libraries must appear on a link line).
+    pub fn dependencies_of(&self, member: &WorkspaceMember) -> ForgeResult<Vec<&WorkspaceMember>> {
+        fn visit<'a>(
+            workspace: &'a Workspace,
+            name: &str,
+            visited: &mut HashSet<String>,
+            order: &mut Vec<&'a WorkspaceMember>,
+        ) -> ForgeResult<()> {
+            let deps = workspace.root_config.workspace.dependencies
+                .get(name)
+                .cloned()
+                .unwrap_or_default();
+
+            for dep_name in deps {
+                if !visited.insert(dep_name.clone()) {
+                    continue;
+                }
+
+                let dep = workspace.members
+                    .iter()
+                    .find(|m| m.name == dep_name)
+                    .ok_or_else(|| ForgeError::Workspace(format!(
+                        "Dependency not found: {}",
+                        dep_name
+                    )))?;
+
+                visit(workspace, &dep.name, visited, order)?;
+                order.push(dep);
+            }
+
+            Ok(())
+        }
+
+        let mut visited = HashSet::new();
+        let mut order = Vec::new();
+        visit(self, &member.name, &mut visited, &mut order)?;
+        order.reverse();
+        Ok(order)
+    }
+
+    /// Depth-first visit in dependency order. `path` holds the members
+    /// currently being visited, so a dependency back onto it closes a cycle.
     fn visit_member<'a>(
         &'a self,
         member: &'a WorkspaceMember,
         graph: &HashMap<String, Vec<String>>,
         visited: &mut HashSet<String>,
-        temp_visited: &mut HashSet<String>,
+        path: &mut Vec<String>,
         order: &mut Vec<&'a WorkspaceMember>,
     ) -> ForgeResult<()> {
-        if temp_visited.contains(&member.name) {
+        if let Some(start) = path.iter().position(|name| name == &member.name) {
+            let cycle: Vec<&str> = path[start..].iter()
+                .map(String::as_str)
+                .chain(std::iter::once(member.name.as_str()))
+                .collect();
             return Err(ForgeError::Workspace(format!(
-                "Circular dependency detected involving {}",
-                member.name
+                "Circular dependency detected: {}",
+                cycle.join(" -> ")
             )));
         }
 
@@ -137,7 +326,7 @@ impl Workspace {
             return Ok(());
         }
 
-        temp_visited.insert(member.name.clone());
+        path.push(member.name.clone());
 
         if let Some(deps) = graph.get(&member.name) {
             for dep_name in deps {
@@ -149,11 +338,11 @@ impl Workspace {
                         dep_name
                     )))?;
 
-                self.visit_member(dep, graph, visited, temp_visited, order)?;
+                self.visit_member(dep, graph, visited, path, order)?;
             }
         }
 
-        temp_visited.remove(&member.name);
+        path.pop();
         visited.insert(member.name.clone());
         order.push(member);
 
@@ -161,34 +350,293 @@ impl Workspace {
     }
 }
 
+/// Member directories relative to the workspace root: `members` with
+/// glob entries (`libs/*`) expanded to the matching directories that have
+/// a `forge.toml`, then, with `auto_discover`, every other directory below
+/// the root that has one. Entries matching an `exclude` pattern are left
+/// out.
+fn member_dirs(root_path: &Path, root_config: &Config) -> ForgeResult<Vec<String>> {
+    let workspace = &root_config.workspace;
+    let mut dirs: Vec<String> = Vec::new();
+
+    for entry in &workspace.members {
+        if entry.contains(['*', '?', '[']) {
+            dirs.extend(expand_member_glob(root_path, entry)?);
+        } else {
+            dirs.push(entry.clone());
+        }
+    }
+
+    if workspace.auto_discover {
+        let build_dir = root_path.join(root_config.paths.build_path());
+        let mut walk = walkdir::WalkDir::new(root_path)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter();
+
+        while let Some(entry) = walk.next() {
+            let entry = entry
+                .map_err(|e| ForgeError::Workspace(format!("Failed to scan for members: {}", e)))?;
+            if !entry.file_type().is_dir() {
+                continue;
+            }
+
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if hidden || entry.path() == build_dir {
+                walk.skip_current_dir();
+                continue;
+            }
+
+            if entry.path().join("forge.toml").is_file() {
+                dirs.push(relative_dir(root_path, entry.path()));
+                // Directories below a member belong to that member
+                walk.skip_current_dir();
+            }
+        }
+    }
+
+    let excludes = workspace.exclude.iter()
+        .map(|pattern| member_glob(pattern))
+        .collect::<ForgeResult<Vec<_>>>()?;
+    let mut seen = HashSet::new();
+    dirs.retain(|dir| {
+        !excludes.iter().any(|glob| glob.is_match(dir)) && seen.insert(dir.clone())
+    });
+    Ok(dirs)
+}
+
+/// Directories matching `pattern` component by component, so wildcards
+/// match within a single directory name.
+fn expand_member_glob(root_path: &Path, pattern: &str) -> ForgeResult<Vec<String>> {
+    let mut matches = vec![root_path.to_path_buf()];
+
+    for component in pattern.split(['/', '\\']).filter(|c| !c.is_empty()) {
+        let mut next = Vec::new();
+        let glob = member_glob(component)?;
+        for dir in &matches {
+            if !component.contains(['*', '?', '[']) {
+                next.push(dir.join(component));
+                continue;
+            }
+
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            let mut found: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+                .filter(|entry| glob.is_match(entry.file_name()))
+                .map(|entry| entry.path())
+                .collect();
+            found.sort();
+            next.extend(found);
+        }
+        matches = next;
+    }
+
+    Ok(matches.iter()
+        .filter(|dir| dir.join("forge.toml").is_file())
+        .map(|dir| relative_dir(root_path, dir))
+        .collect())
+}
+
+fn relative_dir(root_path: &Path, dir: &Path) -> String {
+    let relative = dir.strip_prefix(root_path).unwrap_or(dir);
+    relative.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// A `members` or `exclude` pattern, where `*`, `?` and `[...]` match
+/// within a directory name and `**` spans directories.
+fn member_glob(pattern: &str) -> ForgeResult<GlobMatcher> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|e| ForgeError::Config(format!("Invalid member pattern '{}': {}", pattern, e)))
+}
+
 impl WorkspaceMember {
+    /// Resolves a configured path against the member directory, or against the
+    /// workspace root when it starts with `$WORKSPACE`.
+    pub fn resolve_path(&self, path: &str) -> PathBuf {
+        match path.strip_prefix(WORKSPACE_TOKEN) {
+            Some(rest) => self.workspace_root.join(rest.trim_start_matches(['/', '\\'])),
+            None => self.path.join(path),
+        }
+    }
+
+    /// Path of a source relative to the member, used to lay out its object
+    /// file. Sources pulled in through `$WORKSPACE` are placed under
+    /// `_workspace` so they cannot clash with the member's own directories.
+    pub fn relative_source_path(&self, source: &Path) -> PathBuf {
+        if let Ok(relative) = source.strip_prefix(&self.path) {
+            return relative.to_path_buf();
+        }
+
+        if let Ok(relative) = source.strip_prefix(&self.workspace_root) {
+            return Path::new("_workspace").join(relative);
+        }
+
+        source.components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect()
+    }
+
+    fn validate_paths(&self) -> ForgeResult<()> {
+        let paths = &self.config.paths;
+        let all = std::iter::once(&paths.src)
+            .chain(paths.src_roots.iter())
+            .chain(paths.include.iter())
+            .chain(paths.build.iter());
+
+        for path in all {
+            let Some(index) = path.find(WORKSPACE_TOKEN) else {
+                continue;
+            };
+
+            let rest = &path[index + WORKSPACE_TOKEN.len()..];
+            if index != 0 || !(rest.is_empty() || rest.starts_with(['/', '\\'])) {
+                return Err(ForgeError::Config(format!(
+                    "Invalid path '{}' in member {}: {} must be a leading path component",
+                    path, self.name, WORKSPACE_TOKEN
+                )));
+            }
+        }
+
+        // Cleaning removes the whole build directory
+        let build_dir = self.get_build_dir();
+        if build_dir == self.path || build_dir == self.workspace_root {
+            return Err(ForgeError::Config(format!(
+                "Build directory of member {} must not be the member or workspace directory",
+                self.name
+            )));
+        }
+
+        Ok(())
+    }
+
     pub fn get_source_dir(&self) -> PathBuf {
-        self.path.join(&self.config.paths.src)
+        self.resolve_path(&self.config.paths.src)
+    }
+
+    pub fn get_source_dirs(&self) -> Vec<PathBuf> {
+        std::iter::once(self.get_source_dir())
+            .chain(self.config.paths.src_roots.iter().map(|dir| self.resolve_path(dir)))
+            .collect()
+    }
+
+    /// Whether `path` has one of the member's source extensions.
+    pub fn is_source(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                self.config.paths.source_extensions.iter()
+                    .any(|allowed| allowed.strip_prefix('.').unwrap_or(allowed) == ext)
+            })
     }
 
     pub fn get_include_dirs(&self) -> Vec<PathBuf> {
         self.config.paths.include
             .iter()
-            .map(|dir| self.path.join(dir))
+            .map(|dir| self.resolve_path(dir))
+            .collect()
+    }
+
+    /// The include directories dependent members get to see.
+    pub fn get_public_include_dirs(&self) -> Vec<PathBuf> {
+        self.config.paths.public_include.as_ref()
+            .unwrap_or(&self.config.paths.include)
+            .iter()
+            .map(|dir| self.resolve_path(dir))
             .collect()
     }
 
+    /// Where the member's outputs go: below `--build-dir` or the
+    /// workspace's build directory, unless the member (other than the
+    /// root one) sets a `build` of its own. One repeating the root's
+    /// setting, as `forge init` used to write, doesn't count.
     pub fn get_build_dir(&self) -> PathBuf {
-        self.workspace_root.join(&self.config.paths.build).join(&self.name)
+        match (&self.build_dir, &self.config.paths.build) {
+            (Some(dir), _) => dir.join(&self.name),
+            (None, Some(build))
+                if self.path != self.workspace_root
+                    && self.workspace_root.join(build) != self.workspace_build_dir =>
+            {
+                self.resolve_path(build)
+            }
+            (None, _) => self.workspace_build_dir.join(&self.name),
+        }
     }
 
-    pub fn get_target_path(&self) -> PathBuf {
+    /// The target triple the member is built for: the one selected on the
+    /// command line, its `[cross]` target, or the workspace's
+    /// `default_target`. `None` for native builds.
+    pub fn target_triple(&self) -> Option<&str> {
+        self.selected_target.as_deref()
+            .or_else(|| self.config.cross.as_ref().map(|c| c.target.as_str()))
+            .or(self.default_target.as_deref())
+    }
+
+    /// Output directory for the selected profile (and target), so
+    /// different configurations never share objects or binaries.
+    pub fn get_output_dir(&self) -> PathBuf {
         let mut path = self.get_build_dir();
 
-        if let Some(cross) = &self.config.cross {
-            path = path.join(&cross.target);
+        if let Some(triple) = self.target_triple() {
+            path = path.join(triple);
         }
 
         let profile = self.selected_profile.as_deref()
             .unwrap_or(&self.config.build.default_profile);
-        path = path.join(profile);
+        path.join(profile)
+    }
+
+    pub fn get_object_dir(&self) -> PathBuf {
+        self.get_output_dir().join("obj")
+    }
+
+    pub fn get_target_path(&self) -> PathBuf {
+        let name = &self.config.build.target;
+        let file_name = match self.config.build.output_type {
+            OutputType::Executable => format!("{}{}", name, self.artifact_target().executable_extension()),
+            // Interface members never create theirs
+            OutputType::Interface => name.clone(),
+            OutputType::Static => self.artifact_target().static_library_name(name),
+            OutputType::Shared => self.artifact_target().shared_library_name(name),
+        };
+        self.get_output_dir().join(file_name)
+    }
+
+    fn artifact_target(&self) -> Target {
+        self.target_triple()
+            .and_then(|triple| Target::from_str(triple).ok())
+            .or_else(|| Target::host().ok())
+            .unwrap_or(Target {
+                arch: Architecture::Unknown,
+                vendor: Vendor::Unknown,
+                os: OS::Unknown,
+                env: Environment::None,
+            })
+    }
+
+    /// Test objects and binaries, kept apart per profile and target like
+    /// the member's own.
+    pub fn get_test_dir(&self) -> PathBuf {
+        self.get_output_dir().join("tests")
+    }
+
+    pub fn get_test_binary_path(&self) -> PathBuf {
+        let extension = self.artifact_target().executable_extension();
+        self.get_test_dir().join(format!("{}{}", self.config.build.target, extension))
+    }
 
-        path.join(&self.config.build.target)
+    /// The executable of a single test source in per-file test mode.
+    pub fn get_test_source_binary_path(&self, source: &Path) -> PathBuf {
+        let extension = self.artifact_target().executable_extension();
+        self.get_test_dir().join(self.relative_source_path(source).with_extension(extension.trim_start_matches('.')))
     }
 
     pub fn clean(&self) -> ForgeResult<()> {
@@ -201,4 +649,104 @@ impl WorkspaceMember {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn member_globs_stay_within_a_directory() {
+        let glob = member_glob("lib?/[a-m]*").unwrap();
+        assert!(glob.is_match("libs/math"));
+        assert!(!glob.is_match("libs/net"));
+        assert!(!glob.is_match("libs/math/inner"));
+        assert!(member_glob("[unclosed").is_err());
+    }
+
+    #[test]
+    fn member_excludes_span_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for member in ["app", "libs/math", "libs/experimental", "tools/gen/experimental"] {
+            std::fs::create_dir_all(root.join(member)).unwrap();
+            std::fs::write(root.join(member).join("forge.toml"), "").unwrap();
+        }
+
+        let mut config = Config::default_for_member("root");
+        config.workspace.members = vec!["app".to_string(), "libs/*".to_string()];
+        config.workspace.exclude = vec!["**/experimental".to_string()];
+        config.workspace.auto_discover = true;
+
+        assert_eq!(member_dirs(root, &config).unwrap(), ["app", "libs/math"]);
+    }
+
+    #[test]
+    fn members_repeating_the_root_build_dir_build_into_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(
+            root.join("forge.toml"),
+            "[workspace]\nmembers = [\"old\", \"own\", \"plain\"]\n\n\
+             [build]\ncompiler = \"g++\"\ntarget = \"\"\n\n[paths]\n\n[compiler]\n",
+        ).unwrap();
+        for (member, paths) in [("old", "build = \"build\""), ("own", "build = \"out\""), ("plain", "")] {
+            std::fs::create_dir_all(root.join(member)).unwrap();
+            std::fs::write(
+                root.join(member).join("forge.toml"),
+                format!("[build]\ncompiler = \"g++\"\ntarget = \"{}\"\n\n[paths]\n{}\n\n[compiler]\n", member, paths),
+            ).unwrap();
+        }
+
+        let workspace = Workspace::new(root).unwrap();
+        let build_dir = |name: &str| {
+            workspace.members.iter().find(|m| m.name == name).unwrap().get_build_dir()
+        };
+        assert_eq!(build_dir("old"), root.join("build").join("old"));
+        assert_eq!(build_dir("own"), root.join("own").join("out"));
+        assert_eq!(build_dir("plain"), root.join("build").join("plain"));
+    }
+
+    #[test]
+    fn windows_executables_get_exe() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(
+            root.join("forge.toml"),
+            "[build]\ncompiler = \"g++\"\ntarget = \"app\"\n\n\
+             [cross]\ntarget = \"x86_64-pc-windows-gnu\"\n\n[paths]\n\n[compiler]\n",
+        ).unwrap();
+
+        let mut workspace = Workspace::new(root).unwrap();
+        let member = &workspace.members[0];
+        assert_eq!(member.get_target_path().file_name().unwrap(), "app.exe");
+        assert_eq!(member.get_test_binary_path().file_name().unwrap(), "app.exe");
+
+        workspace.set_target(Some("x86_64-unknown-linux-gnu".to_string()));
+        assert_eq!(workspace.members[0].get_target_path().file_name().unwrap(), "app");
+    }
+
+    #[test]
+    fn reports_every_invalid_dependency() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for member in ["app", "core"] {
+            std::fs::create_dir_all(root.join(member)).unwrap();
+        }
+        std::fs::write(
+            root.join("forge.toml"),
+            "[workspace]\nmembers = [\"app\", \"core\"]\n\n\
+             [workspace.dependencies]\napp = [\"core\", \"app\", \"net\"]\napq = [\"core\"]\n\n\
+             [build]\ncompiler = \"g++\"\ntarget = \"\"\n\n[paths]\n\n[compiler]\n",
+        ).unwrap();
+
+        let Err(ForgeError::Workspace(message)) = Workspace::new(root) else {
+            panic!("expected invalid dependencies to be reported");
+        };
+        assert_eq!(
+            message,
+            "Invalid dependencies: app depends on itself, app depends on unknown member net, \
+             dependencies declared for unknown member apq"
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Workspaces for the integration tests, compiled and linked by a stub
+//! compiler so that no real toolchain is needed.
+#![allow(dead_code)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// A GCC stand-in: compiles by touching the object, and links by touching
+/// the output and logging its name to `links.log` next to the script (and
+/// its arguments to `link-args.log`).
+/// Version queries are logged to `versions.log`. Every compile logs how
+/// many compiles were running to `running.log`,
+/// and takes a while when a `slow` file is next to the script.
+const STUB_COMPILER: &str = r#"#!/bin/sh
+out=""
+compile=no
+prev=""
+for arg in "$@"; do
+    [ "$arg" = "--version" ] && { echo "stub 1.0"; echo >> "$(dirname "$0")/versions.log"; exit 0; }
+    [ "$arg" = "-c" ] && compile=yes
+    [ "$prev" = "-o" ] && out="$arg"
+    prev="$arg"
+done
+[ -n "$out" ] || exit 0
+dir="$(dirname "$0")"
+if [ "$compile" = yes ]; then
+    mkdir -p "$dir/running"
+    touch "$dir/running/$$"
+    ls "$dir/running" | wc -l >> "$dir/running.log"
+    [ -e "$dir/slow" ] && sleep 0.2
+    rm "$dir/running/$$"
+fi
+mkdir -p "$(dirname "$out")"
+touch "$out"
+[ "$compile" = yes ] && exit 0
+basename "$out" >> "$dir/links.log"
+echo "$@" >> "$dir/link-args.log"
+"#;
+
+pub fn write(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+/// Writes the stub compiler into `dir` and returns its path.
+pub fn stub_compiler(dir: &Path) -> PathBuf {
+    let compiler = dir.join("stub-g++");
+    write(&compiler, STUB_COMPILER);
+    fs::set_permissions(&compiler, fs::Permissions::from_mode(0o755)).unwrap();
+    compiler
+}
+
+/// How often the stub next to `compiler` linked `member`.
+pub fn links(compiler: &Path, member: &str) -> usize {
+    fs::read_to_string(compiler.with_file_name("links.log"))
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| *line == member)
+        .count()
+}
+
+/// The most compiles the stub next to `compiler` ran at once.
+pub fn peak_compiles(compiler: &Path) -> usize {
+    fs::read_to_string(compiler.with_file_name("running.log"))
+        .unwrap_or_default()
+        .lines()
+        .map(|line| line.trim().parse::<usize>().unwrap())
+        .max()
+        .unwrap_or(0)
+}
+
+/// How often the stub next to `compiler` was asked for its version.
+pub fn version_queries(compiler: &Path) -> usize {
+    fs::read_to_string(compiler.with_file_name("versions.log"))
+        .unwrap_or_default()
+        .lines()
+        .count()
+}
+
+/// How many compiles the stub next to `compiler` ran.
+pub fn compiles(compiler: &Path) -> usize {
+    fs::read_to_string(compiler.with_file_name("running.log"))
+        .unwrap_or_default()
+        .lines()
+        .count()
+}
+
+/// Makes every compile of the stub next to `compiler` take a while, so
+/// that parallel ones overlap.
+pub fn slow_compiles(compiler: &Path) {
+    write(&compiler.with_file_name("slow"), "");
+}
+
+/// The arguments of every link the stub next to `compiler` ran.
+pub fn link_args(compiler: &Path) -> Vec<Vec<String>> {
+    fs::read_to_string(compiler.with_file_name("link-args.log"))
+        .unwrap_or_default()
+        .lines()
+        .map(|line| line.split(' ').map(String::from).collect())
+        .collect()
+}
+
+/// A workspace at `root` of executables named `members`, each with a
+/// header included by its only source.
+pub fn workspace(root: &Path, members: &[&str], compiler: &Path) {
+    let list: Vec<String> = members.iter().map(|name| format!("\"{}\"", name)).collect();
+    write(
+        &root.join("forge.toml"),
+        &format!(
+            "[workspace]\nmembers = [{}]\n\n[build]\ncompiler = \"{}\"\ntarget = \"\"\n\n[paths]\n\n[compiler]\n",
+            list.join(", "),
+            compiler.display()
+        ),
+    );
+
+    for name in members {
+        write(
+            &root.join(name).join("forge.toml"),
+            &format!(
+                "[build]\ncompiler = \"{}\"\ntarget = \"{}\"\n\n[paths]\nsrc = \"src\"\ninclude = [\"include\"]\n\n[compiler]\nflags = []\n",
+                compiler.display(),
+                name
+            ),
+        );
+        write(&root.join(name).join("include").join(format!("{}.hpp", name)), "int value();\n");
+        write(
+            &root.join(name).join("src").join("main.cpp"),
+            &format!("#include \"{}.hpp\"\nint main() {{ return 0; }}\n", name),
+        );
+    }
+}
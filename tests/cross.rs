@@ -0,0 +1,40 @@
+//! Cross builds with a stub toolchain.
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+
+use common::{links, stub_compiler, write};
+use forge::{Builder, Workspace};
+
+#[test]
+fn windows_gnu_builds_an_exe_with_mingw() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let toolchain = root.join("mingw");
+    let stub = stub_compiler(root);
+    let compiler = toolchain.join("bin/x86_64-w64-mingw32-g++");
+    fs::create_dir_all(compiler.parent().unwrap()).unwrap();
+    fs::rename(&stub, &compiler).unwrap();
+
+    write(
+        &root.join("forge.toml"),
+        &format!(
+            "[build]\ncompiler = \"g++\"\ntarget = \"app\"\n\n\
+             [cross]\ntarget = \"x86_64-pc-windows-gnu\"\ntoolchain = \"{}\"\n\n\
+             [paths]\nsrc = \"src\"\ninclude = []\n\n[compiler]\nflags = []\n",
+            toolchain.display()
+        ),
+    );
+    write(&root.join("src/main.cpp"), "int main() { return 0; }\n");
+
+    let workspace = Workspace::new(root).unwrap();
+    let members = workspace.selected_members(&[]);
+    Builder::new(workspace.clone(), None, None, None, None).unwrap().build(&members).unwrap();
+
+    let output = workspace.members[0].get_target_path();
+    assert!(output.ends_with("x86_64-pc-windows-gnu/debug/app.exe"), "{}", output.display());
+    assert!(output.exists());
+    assert_eq!(links(&compiler, "app.exe"), 1);
+}
@@ -0,0 +1,131 @@
+//! Rebuilds of a workspace after an edit.
+#![cfg(unix)]
+
+mod common;
+
+use std::fs::{self, File};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use common::{compiles, link_args, links, stub_compiler, version_queries, workspace, write};
+use forge::{Builder, Workspace};
+
+fn build(root: &Path) -> Vec<(String, bool)> {
+    build_profile(root, None)
+}
+
+fn build_profile(root: &Path, profile: Option<&str>) -> Vec<(String, bool)> {
+    let workspace = Workspace::new(root).unwrap();
+    let members = workspace.selected_members(&[]);
+    let builder = Builder::new(workspace.clone(), None, None, None, profile).unwrap();
+    builder.build(&members).unwrap()
+        .members
+        .into_iter()
+        .map(|member| (member.name, member.up_to_date))
+        .collect()
+}
+
+#[test]
+fn header_edit_only_relinks_its_member() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let compiler = stub_compiler(root);
+    workspace(root, &["a", "b", "c"], &compiler);
+
+    build(root);
+    for name in ["a", "b", "c"] {
+        assert_eq!(links(&compiler, name), 1, "first build links {}", name);
+    }
+
+    write(&root.join("a/include/a.hpp"), "int value();\nint other();\n");
+    let report = build(root);
+    assert_eq!(links(&compiler, "a"), 2);
+    assert_eq!(links(&compiler, "b"), 1);
+    assert_eq!(links(&compiler, "c"), 1);
+    assert!(report.contains(&("a".to_string(), false)));
+    assert!(report.contains(&("b".to_string(), true)));
+    assert!(report.contains(&("c".to_string(), true)));
+}
+
+#[test]
+fn hash_cache_mode_ignores_touches() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let compiler = stub_compiler(root);
+    workspace(root, &["a"], &compiler);
+    let config = fs::read_to_string(root.join("forge.toml")).unwrap();
+    fs::write(root.join("forge.toml"), config.replace("[build]\n", "[build]\ncache_mode = \"hash\"\n")).unwrap();
+
+    build(root);
+    assert_eq!(compiles(&compiler), 1);
+
+    let source = root.join("a/src/main.cpp");
+    let later = SystemTime::now() + Duration::from_secs(10);
+    File::options().write(true).open(&source).unwrap().set_modified(later).unwrap();
+    assert_eq!(build(root), [("a".to_string(), true)]);
+    assert_eq!(compiles(&compiler), 1);
+
+    write(&source, "#include \"a.hpp\"\nint main() { return 1; }\n");
+    assert_eq!(build(root), [("a".to_string(), false)]);
+    assert_eq!(compiles(&compiler), 2);
+}
+
+#[test]
+fn same_named_sources_get_their_own_objects() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let compiler = stub_compiler(root);
+    workspace(root, &["a"], &compiler);
+    write(&root.join("a/src/x/util.cpp"), "int x() { return 0; }\n");
+    write(&root.join("a/src/y/util.cpp"), "int y() { return 0; }\n");
+
+    build(root);
+    assert_eq!(compiles(&compiler), 3);
+    let args = &link_args(&compiler)[0];
+    for object in ["x/util.o", "y/util.o"] {
+        let linked = args.iter().find(|arg| arg.ends_with(object)).expect(object);
+        assert!(Path::new(linked).exists(), "{}", linked);
+    }
+}
+
+#[test]
+fn profiles_keep_their_own_objects() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let compiler = stub_compiler(root);
+    workspace(root, &["a"], &compiler);
+    let config = fs::read_to_string(root.join("a/forge.toml")).unwrap();
+    fs::write(root.join("a/forge.toml"), config + "\n[profiles.release]\nopt_level = \"2\"\ndebug_info = false\nlto = false\n").unwrap();
+
+    build_profile(root, Some("debug"));
+    build_profile(root, Some("release"));
+    assert_eq!(compiles(&compiler), 2);
+
+    for profile in ["debug", "release", "debug", "release"] {
+        assert_eq!(build_profile(root, Some(profile)), [("a".to_string(), true)], "{}", profile);
+    }
+    assert_eq!(compiles(&compiler), 2);
+    assert_eq!(links(&compiler, "a"), 2);
+}
+
+#[test]
+fn build_info_is_kept_while_up_to_date() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let compiler = stub_compiler(root);
+    workspace(root, &["a"], &compiler);
+
+    build(root);
+    // The cache and build-info.json share one query
+    assert_eq!(version_queries(&compiler), 1);
+    let info = root.join("build/a/debug/build-info.json");
+    let written = fs::metadata(&info).unwrap().modified().unwrap();
+    File::options().write(true).open(&info).unwrap().set_modified(SystemTime::UNIX_EPOCH).unwrap();
+
+    assert_eq!(build(root), [("a".to_string(), true)]);
+    assert_eq!(fs::metadata(&info).unwrap().modified().unwrap(), SystemTime::UNIX_EPOCH);
+
+    write(&root.join("a/src/main.cpp"), "#include \"a.hpp\"\nint main() { return 10; }\n");
+    assert_eq!(build(root), [("a".to_string(), false)]);
+    assert!(fs::metadata(&info).unwrap().modified().unwrap() >= written);
+}
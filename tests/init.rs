@@ -0,0 +1,28 @@
+//! Projects created by `forge init`.
+#![cfg(unix)]
+
+mod common;
+
+use std::process::Command;
+
+use common::stub_compiler;
+
+#[test]
+fn initialized_project_builds() {
+    let dir = tempfile::tempdir().unwrap();
+    let compiler = stub_compiler(dir.path());
+    let project = dir.path().join("proj");
+
+    let forge = env!("CARGO_BIN_EXE_forge");
+    let init = Command::new(forge).arg("init").arg(&project).output().unwrap();
+    assert!(init.status.success(), "{}", String::from_utf8_lossy(&init.stderr));
+
+    let build = Command::new(forge)
+        .args(["build", "--path"])
+        .arg(&project)
+        .env("FORGE_CXX", &compiler)
+        .output()
+        .unwrap();
+    assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+    assert_eq!(common::links(&compiler, "proj"), 1);
+}
@@ -0,0 +1,30 @@
+//! The `jobs` setting of forge.toml. Kept apart from the other tests, as
+//! it configures the process's global thread pool.
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+
+use common::{peak_compiles, slow_compiles, stub_compiler, workspace, write};
+use forge::{Builder, Workspace};
+
+#[test]
+fn configured_jobs_limit_concurrent_compiles() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let compiler = stub_compiler(root);
+    slow_compiles(&compiler);
+    workspace(root, &["app"], &compiler);
+    for i in 0..8 {
+        write(&root.join("app/src").join(format!("part{}.cpp", i)), "int part() { return 0; }\n");
+    }
+    let config = fs::read_to_string(root.join("forge.toml")).unwrap();
+    fs::write(root.join("forge.toml"), config.replace("[build]\n", "[build]\njobs = 2\n")).unwrap();
+
+    let workspace = Workspace::new(root).unwrap();
+    let members = workspace.selected_members(&[]);
+    Builder::new(workspace.clone(), None, None, None, None).unwrap().build(&members).unwrap();
+
+    assert_eq!(peak_compiles(&compiler), 2);
+}
@@ -0,0 +1,31 @@
+//! Members without dependency edges between them building at once. In a
+//! test binary of its own, as it sizes the global thread pool.
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+
+use common::{links, peak_compiles, slow_compiles, stub_compiler, workspace};
+use forge::{Builder, Workspace};
+
+#[test]
+fn independent_members_build_concurrently() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let compiler = stub_compiler(root);
+    slow_compiles(&compiler);
+    workspace(root, &["a", "b", "c"], &compiler);
+    // Enough threads for every member even on a single core
+    let config = fs::read_to_string(root.join("forge.toml")).unwrap();
+    fs::write(root.join("forge.toml"), config.replace("[build]\n", "[build]\njobs = 3\n")).unwrap();
+
+    let workspace = Workspace::new(root).unwrap();
+    let members = workspace.selected_members(&[]);
+    Builder::new(workspace.clone(), None, None, None, None).unwrap().build(&members).unwrap();
+
+    assert_eq!(peak_compiles(&compiler), 3);
+    for name in ["a", "b", "c"] {
+        assert_eq!(links(&compiler, name), 1, "{}", name);
+    }
+}
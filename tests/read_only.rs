@@ -0,0 +1,59 @@
+//! Building a checkout that can't be written to, with every output
+//! redirected out of it.
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use common::{links, stub_compiler, workspace};
+use forge::{Builder, Workspace};
+use walkdir::WalkDir;
+
+fn files(root: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkDir::new(root)
+        .into_iter()
+        .map(|entry| entry.unwrap().into_path())
+        .collect();
+    files.sort();
+    files
+}
+
+fn set_mode(root: &Path, mode: u32) {
+    for path in files(root) {
+        if path.is_dir() {
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        }
+    }
+}
+
+#[test]
+fn builds_from_read_only_root() {
+    let tools = tempfile::tempdir().unwrap();
+    let compiler = stub_compiler(tools.path());
+    let checkout = tempfile::tempdir().unwrap();
+    let root = checkout.path();
+    workspace(root, &["app", "lib"], &compiler);
+    let out = tempfile::tempdir().unwrap();
+
+    set_mode(root, 0o555);
+    let before = files(root);
+
+    let mut workspace = Workspace::new(root).unwrap();
+    workspace.set_build_dir(Some(out.path().join("build")));
+    let members = workspace.selected_members(&[]);
+    let mut builder = Builder::new(workspace.clone(), None, None, None, None).unwrap();
+    builder.set_cache_dir(&out.path().join("cache"));
+    let result = builder.build(&members);
+
+    let after = files(root);
+    set_mode(root, 0o755);
+    result.unwrap();
+
+    assert_eq!(before, after, "the build wrote into the checkout");
+    assert_eq!(links(&compiler, "app"), 1);
+    assert_eq!(links(&compiler, "lib"), 1);
+    assert!(out.path().join("cache").read_dir().unwrap().next().is_some());
+}